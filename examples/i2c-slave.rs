@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+/**
+  Exposes a bank of registers to an I2C master at address 0x42
+
+  The master writes the register index followed by the new values, or writes the index and then
+  reads the values.
+*/
+use panic_halt as _;
+
+use cortex_m_rt::entry;
+use stm32f1xx_hal::{
+    i2c::{
+        slave::{Config, Direction, Event},
+        Address, I2cSlave,
+    },
+    pac,
+    prelude::*,
+};
+
+#[entry]
+fn main() -> ! {
+    let dp = pac::Peripherals::take().unwrap();
+
+    let mut flash = dp.FLASH.constrain();
+    let rcc = dp.RCC.constrain();
+    let clocks = rcc.cfgr.freeze(&mut flash.acr);
+
+    let mut afio = dp.AFIO.constrain();
+    let mut gpiob = dp.GPIOB.split();
+
+    let scl = gpiob.pb6.into_alternate_open_drain(&mut gpiob.crl);
+    let sda = gpiob.pb7.into_alternate_open_drain(&mut gpiob.crl);
+
+    let config = Config::new(Address::SevenBit(0x42));
+    let mut slave = I2cSlave::i2c1(dp.I2C1, (scl, sda), &mut afio.mapr, config, clocks);
+
+    let mut registers = [0u8; 16];
+    let mut index = 0;
+    // The first byte of a write selects the register
+    let mut first_byte = false;
+
+    loop {
+        match slave.poll() {
+            Ok(Event::AddressMatched(direction)) => first_byte = direction == Direction::Write,
+            Ok(Event::ByteReceived(byte)) => {
+                if first_byte {
+                    index = byte as usize % registers.len();
+                    first_byte = false;
+                } else {
+                    registers[index] = byte;
+                    index = (index + 1) % registers.len();
+                }
+            }
+            Ok(Event::ByteRequested) => {
+                slave.write(registers[index]);
+                index = (index + 1) % registers.len();
+            }
+            Ok(Event::Stop) | Err(_) => {}
+        }
+    }
+}
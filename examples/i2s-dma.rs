@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+/**
+  Streams a square wave to an I2S DAC using circular DMA
+*/
+use panic_halt as _;
+
+use cortex_m::singleton;
+use cortex_m_rt::entry;
+use stm32f1xx_hal::{
+    pac,
+    prelude::*,
+    spi::i2s::{Config, I2s},
+};
+
+#[entry]
+fn main() -> ! {
+    let dp = pac::Peripherals::take().unwrap();
+
+    let mut flash = dp.FLASH.constrain();
+    let rcc = dp.RCC.constrain();
+
+    let clocks = rcc
+        .cfgr
+        .use_hse(8.mhz())
+        .sysclk(72.mhz())
+        .freeze(&mut flash.acr);
+
+    let mut gpiob = dp.GPIOB.split();
+    let mut gpioc = dp.GPIOC.split();
+
+    // WS, CK, SD and MCK
+    let pins = (
+        gpiob.pb12.into_alternate_push_pull(&mut gpiob.crh),
+        gpiob.pb13.into_alternate_push_pull(&mut gpiob.crh),
+        gpiob.pb15.into_alternate_push_pull(&mut gpiob.crh),
+        gpioc.pc6.into_alternate_push_pull(&mut gpioc.crl),
+    );
+
+    let config = Config::default().sample_rate(48.khz());
+    let i2s = I2s::i2s2(dp.SPI2, pins, config, clocks);
+
+    let dma = dp.DMA1.split();
+    let i2s_dma = i2s.with_tx_dma(dma.5);
+
+    // Each half holds 32 stereo samples: the left and the right half-word in turn
+    let buffer = singleton!(: [[u16; 64]; 2] = [[0; 64]; 2]).unwrap();
+
+    let mut circ_buffer = i2s_dma.circ_write(buffer);
+
+    let mut level = 0x2000;
+    loop {
+        // Refill each half once it has been sent, toggling the level every 32 samples
+        if circ_buffer.poke(|half, _| half.fill(level)).is_ok() {
+            level = !level;
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Outputs a delayed pulse on PA7 after each rising edge on PA6, e.g. to fire a triac some time
+//! after the zero crossing of the mains voltage
+
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use cortex_m::asm;
+use cortex_m_rt::entry;
+use stm32f1xx_hal::{
+    compare::{Channel, Polarity},
+    pac,
+    prelude::*,
+    timer::{Tim3NoRemap, Timer},
+};
+
+#[entry]
+fn main() -> ! {
+    let p = pac::Peripherals::take().unwrap();
+
+    let mut flash = p.FLASH.constrain();
+    let rcc = p.RCC.constrain();
+
+    let clocks = rcc.cfgr.freeze(&mut flash.acr);
+
+    let mut afio = p.AFIO.constrain();
+
+    let mut gpioa = p.GPIOA.split();
+
+    // TIM3 CH2 outputs the pulse, CH1 is the trigger input
+    let output = gpioa.pa7.into_alternate_push_pull(&mut gpioa.crl);
+    let zero_cross = gpioa.pa6.into_floating_input(&mut gpioa.crl);
+
+    let mut pulse = Timer::tim3(p.TIM3, &clocks).one_pulse::<Tim3NoRemap, _, _, _>(
+        output,
+        &mut afio.mapr,
+        1.mhz(),
+    );
+    pulse.set_trigger(zero_cross, Polarity::Rising);
+
+    // 2 ms after the edge, 100 us long
+    pulse.set_pulse(2_000, 100);
+    pulse.enable(Channel::C2);
+
+    loop {
+        asm::wfi();
+    }
+}
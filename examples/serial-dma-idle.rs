@@ -0,0 +1,56 @@
+//! Serial interface variable-length frame reception using circular DMA and idle line detection
+
+#![deny(unsafe_code)]
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+
+use cortex_m::singleton;
+
+use cortex_m_rt::entry;
+use stm32f1xx_hal::{
+    pac,
+    prelude::*,
+    serial::{Config, Serial},
+};
+
+#[entry]
+fn main() -> ! {
+    let p = pac::Peripherals::take().unwrap();
+
+    let mut flash = p.FLASH.constrain();
+    let rcc = p.RCC.constrain();
+
+    let clocks = rcc.cfgr.freeze(&mut flash.acr);
+
+    let mut afio = p.AFIO.constrain();
+    let channels = p.DMA1.split();
+
+    let mut gpioa = p.GPIOA.split();
+
+    // USART1
+    let tx = gpioa.pa9.into_alternate_push_pull(&mut gpioa.crh);
+    let rx = gpioa.pa10;
+
+    let serial = Serial::usart1(
+        p.USART1,
+        (tx, rx),
+        &mut afio.mapr,
+        Config::default().baudrate(9_600.bps()),
+        clocks,
+    );
+
+    let rx = serial.split().1.with_dma(channels.5);
+    let buf = singleton!(: [u8; 64] = [0; 64]).unwrap();
+
+    let mut reader = rx.frame_reader(buf);
+
+    // Usually `poll` is called from the USART1 and DMA1_CHANNEL5 interrupt handlers
+    let mut line = [0; 64];
+    loop {
+        if let Ok(Some(frame)) = reader.poll() {
+            let _len = frame.copy_to(&mut line);
+        }
+    }
+}
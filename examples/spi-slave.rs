@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+/**
+  Exchanges data with an SPI master using DMA
+*/
+use panic_halt as _;
+
+use cortex_m::singleton;
+use cortex_m_rt::entry;
+use stm32f1xx_hal::{
+    pac,
+    prelude::*,
+    spi::{Mode, Phase, Polarity, Spi},
+};
+
+#[entry]
+fn main() -> ! {
+    let dp = pac::Peripherals::take().unwrap();
+
+    // Acquire the GPIOB peripheral
+    let mut gpiob = dp.GPIOB.split();
+
+    // The clock, MOSI and NSS are driven by the master
+    let pins = (
+        gpiob.pb13.into_floating_input(&mut gpiob.crh),
+        gpiob.pb14.into_alternate_push_pull(&mut gpiob.crh),
+        gpiob.pb15.into_floating_input(&mut gpiob.crh),
+        gpiob.pb12.into_floating_input(&mut gpiob.crh),
+    );
+
+    let spi_mode = Mode {
+        polarity: Polarity::IdleLow,
+        phase: Phase::CaptureOnFirstTransition,
+    };
+    let spi = Spi::spi2_slave(dp.SPI2, pins, spi_mode);
+
+    // Set up the DMA device
+    let dma = dp.DMA1.split();
+
+    // Connect the SPI device to the DMA
+    let spi_dma = spi.with_rx_tx_dma(dma.4, dma.5);
+
+    let rx_buffer = singleton!(: [u8; 8] = [0; 8]).unwrap();
+    let tx_buffer = singleton!(: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+
+    // The transfer completes once the master has clocked out 8 bytes
+    let transfer = spi_dma.read_write(rx_buffer, tx_buffer);
+    let ((_rx_buffer, _tx_buffer), _spi_dma) = transfer.wait();
+
+    loop {}
+}
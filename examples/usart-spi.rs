@@ -0,0 +1,44 @@
+//! Uses USART1 in synchronous mode as an additional SPI master
+//!
+//! Connect a shift register or display to PA8 (SCK), PA9 (MOSI) and PA10 (MISO).
+
+#![deny(unsafe_code)]
+#![no_std]
+#![no_main]
+
+use cortex_m_rt::entry;
+use panic_halt as _;
+
+use embedded_hal::spi::MODE_0;
+use stm32f1xx_hal::{pac, prelude::*, serial::UsartSpi};
+
+#[entry]
+fn main() -> ! {
+    let dp = pac::Peripherals::take().unwrap();
+
+    let mut flash = dp.FLASH.constrain();
+    let rcc = dp.RCC.constrain();
+
+    let clocks = rcc.cfgr.freeze(&mut flash.acr);
+
+    let mut afio = dp.AFIO.constrain();
+    let mut gpioa = dp.GPIOA.split();
+
+    let tx = gpioa.pa9.into_alternate_push_pull(&mut gpioa.crh);
+    let rx = gpioa.pa10;
+    let ck = gpioa.pa8.into_alternate_push_pull(&mut gpioa.crh);
+
+    let mut spi = UsartSpi::usart1(
+        dp.USART1,
+        (tx, rx, ck),
+        &mut afio.mapr,
+        MODE_0,
+        1_u32.mhz(),
+        clocks,
+    );
+
+    let mut buffer = [0xde, 0xad, 0xbe, 0xef];
+    spi.transfer(&mut buffer).unwrap();
+
+    loop {}
+}
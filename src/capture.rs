@@ -0,0 +1,676 @@
+/*!
+  # Input capture
+
+  The timers `TIM1` to `TIM5` and `TIM8` can latch their counter into the capture/compare
+  register of a channel when an edge occurs on the input of the channel. The time between two
+  edges, e.g. the length of an IR remote pulse or of an ultrasonic sensor echo, is the difference
+  of two captured values.
+
+  The counter runs freely over the full 16-bit range at the frequency passed to `capture`, so the
+  difference of two captures is computed with `wrapping_sub`. Each channel has its own edge
+  polarity, prescaler and digital filter, which are set with [`InputCapture::configure`] before
+  splitting the channels.
+
+  ```rust
+  let pins = (gpioa.pa0, gpioa.pa1);
+
+  let mut capture = Timer::tim2(dp.TIM2, &clocks)
+      .capture::<Tim2NoRemap, _, _, _>(pins, &mut afio.mapr, 1.mhz());
+  capture.configure(Channel::C2, Config::default().polarity(Polarity::Falling));
+  let (mut rising, mut falling) = capture.split();
+  rising.enable();
+  falling.enable();
+
+  let start = block!(rising.capture()).unwrap();
+  let end = block!(falling.capture()).unwrap();
+  let pulse_us = end.wrapping_sub(start);
+  ```
+
+  With `with_dma`, the captures of a channel are streamed into a buffer by the CCx DMA request:
+
+  | Channel | TIM1    | TIM2    | TIM3    | TIM4    | TIM5    | TIM8    |
+  |:-------:|:-------:|:-------:|:-------:|:-------:|:-------:|:-------:|
+  | CH1     | DMA1 C2 | DMA1 C5 | DMA1 C6 | DMA1 C1 | DMA2 C5 | DMA2 C3 |
+  | CH2     | DMA1 C3 | DMA1 C7 |         | DMA1 C4 | DMA2 C4 | DMA2 C5 |
+  | CH3     | DMA1 C6 | DMA1 C1 | DMA1 C2 | DMA1 C5 | DMA2 C2 | DMA2 C1 |
+  | CH4     | DMA1 C4 | DMA1 C7 | DMA1 C3 |         | DMA2 C1 | DMA2 C2 |
+*/
+
+use core::marker::PhantomData;
+use core::sync::atomic::{self, Ordering};
+
+use embedded_dma::StaticWriteBuffer;
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+use crate::pac::TIM1;
+#[cfg(feature = "medium")]
+use crate::pac::TIM4;
+#[cfg(any(feature = "high", feature = "connectivity"))]
+use crate::pac::TIM5;
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+use crate::pac::TIM8;
+use crate::pac::{TIM2, TIM3};
+use cast::u16;
+
+use crate::afio::MAPR;
+use crate::bb;
+#[cfg(any(feature = "high", feature = "connectivity"))]
+use crate::dma::dma2;
+use crate::dma::{dma1, CircBuffer, Receive, RxDma, Transfer, TransferPayload, W};
+use crate::gpio::{self, Input};
+use crate::time::Hertz;
+use crate::timer::Timer;
+
+pub use crate::pwm::{Channel, C1, C2, C3, C4};
+
+/// Pin modes which can be used as capture input
+pub trait InputMode {}
+
+impl<MODE> InputMode for Input<MODE> {}
+
+pub trait Pins<REMAP, P> {
+    const C1: bool = false;
+    const C2: bool = false;
+    const C3: bool = false;
+    const C4: bool = false;
+    type Channels;
+
+    fn check_used(c: Channel) -> Channel {
+        if (c == Channel::C1 && Self::C1)
+            || (c == Channel::C2 && Self::C2)
+            || (c == Channel::C3 && Self::C3)
+            || (c == Channel::C4 && Self::C4)
+        {
+            c
+        } else {
+            panic!("Unused channel")
+        }
+    }
+
+    fn split() -> Self::Channels;
+}
+
+use crate::timer::sealed::{Ch1, Ch2, Ch3, Ch4, Remap};
+macro_rules! pins_impl {
+    ( $( ( $($PINX:ident),+ ), ( $($TRAIT:ident),+ ), ( $($ENCHX:ident),+ ); )+ ) => {
+        $(
+            #[allow(unused_parens)]
+            impl<TIM, REMAP, $($PINX,)+> Pins<REMAP, ($($ENCHX),+)> for ($($PINX),+)
+            where
+                REMAP: Remap<Periph = TIM>,
+                $($PINX: $TRAIT<REMAP> + gpio::PinExt, <$PINX as gpio::PinExt>::Mode: InputMode,)+
+            {
+                $(const $ENCHX: bool = true;)+
+                type Channels = ($(CaptureChannel<TIM, $ENCHX>),+);
+                fn split() -> Self::Channels {
+                    ($(CaptureChannel::<TIM, $ENCHX> { _channel: PhantomData, _tim: PhantomData }),+)
+                }
+            }
+        )+
+    };
+}
+
+pins_impl!(
+    (P1, P2, P3, P4), (Ch1, Ch2, Ch3, Ch4), (C1, C2, C3, C4);
+    (P2, P3, P4), (Ch2, Ch3, Ch4), (C2, C3, C4);
+    (P1, P3, P4), (Ch1, Ch3, Ch4), (C1, C3, C4);
+    (P1, P2, P4), (Ch1, Ch2, Ch4), (C1, C2, C4);
+    (P1, P2, P3), (Ch1, Ch2, Ch3), (C1, C2, C3);
+    (P3, P4), (Ch3, Ch4), (C3, C4);
+    (P2, P4), (Ch2, Ch4), (C2, C4);
+    (P2, P3), (Ch2, Ch3), (C2, C3);
+    (P1, P4), (Ch1, Ch4), (C1, C4);
+    (P1, P3), (Ch1, Ch3), (C1, C3);
+    (P1, P2), (Ch1, Ch2), (C1, C2);
+    (P1), (Ch1), (C1);
+    (P2), (Ch2), (C2);
+    (P3), (Ch3), (C3);
+    (P4), (Ch4), (C4);
+);
+
+/// Edge which triggers a capture
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Polarity {
+    Rising,
+    Falling,
+}
+
+/// Number of edges per capture
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Prescaler {
+    Div1 = 0,
+    Div2 = 1,
+    Div4 = 2,
+    Div8 = 3,
+}
+
+/// Channel configuration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    pub polarity: Polarity,
+    pub prescaler: Prescaler,
+    /// Digital filter (ICxF), from 0 (no filter) to 15. An edge is only seen once the input has
+    /// been stable for some samples of the timer clock, see the reference manual.
+    pub filter: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            polarity: Polarity::Rising,
+            prescaler: Prescaler::Div1,
+            filter: 0,
+        }
+    }
+}
+
+impl Config {
+    pub fn polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    pub fn prescaler(mut self, prescaler: Prescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+
+    pub fn filter(mut self, filter: u8) -> Self {
+        assert!(filter < 16);
+        self.filter = filter;
+        self
+    }
+}
+
+/// Capture error
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// A capture happened before the previous one was read, which has been lost
+    Overcapture,
+}
+
+/// Timer in input capture mode
+pub struct InputCapture<TIM, REMAP, P, PINS>
+where
+    REMAP: Remap<Periph = TIM>,
+    PINS: Pins<REMAP, P>,
+{
+    clk: Hertz,
+    _pins: PhantomData<(TIM, REMAP, P, PINS)>,
+}
+
+impl<TIM, REMAP, P, PINS> InputCapture<TIM, REMAP, P, PINS>
+where
+    REMAP: Remap<Periph = TIM>,
+    PINS: Pins<REMAP, P>,
+{
+    /// Returns the frequency the counter runs at
+    pub fn frequency(&self) -> Hertz {
+        self.clk
+    }
+
+    pub fn split(self) -> PINS::Channels {
+        PINS::split()
+    }
+}
+
+/// Capture channel of a timer
+pub struct CaptureChannel<TIM, CHANNEL> {
+    _channel: PhantomData<CHANNEL>,
+    _tim: PhantomData<TIM>,
+}
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+impl Timer<TIM1> {
+    /// Starts the counter at `freq` and configures the channels of `pins` as capture inputs
+    pub fn capture<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> InputCapture<TIM1, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM1>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| unsafe { w.tim1_remap().bits(REMAP::REMAP) });
+
+        let Self { tim, clk } = self;
+        tim1(tim, freq.into(), clk)
+    }
+}
+
+impl Timer<TIM2> {
+    /// Starts the counter at `freq` and configures the channels of `pins` as capture inputs
+    pub fn capture<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> InputCapture<TIM2, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM2>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| unsafe { w.tim2_remap().bits(REMAP::REMAP) });
+
+        let Self { tim, clk } = self;
+        tim2(tim, freq.into(), clk)
+    }
+}
+
+impl Timer<TIM3> {
+    /// Starts the counter at `freq` and configures the channels of `pins` as capture inputs
+    pub fn capture<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> InputCapture<TIM3, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM3>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| unsafe { w.tim3_remap().bits(REMAP::REMAP) });
+
+        let Self { tim, clk } = self;
+        tim3(tim, freq.into(), clk)
+    }
+}
+
+#[cfg(feature = "medium")]
+impl Timer<TIM4> {
+    /// Starts the counter at `freq` and configures the channels of `pins` as capture inputs
+    pub fn capture<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> InputCapture<TIM4, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM4>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| w.tim4_remap().bit(REMAP::REMAP == 1));
+
+        let Self { tim, clk } = self;
+        tim4(tim, freq.into(), clk)
+    }
+}
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+impl Timer<TIM5> {
+    /// Starts the counter at `freq` and configures the channels of `pins` as capture inputs
+    pub fn capture<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        freq: T,
+    ) -> InputCapture<TIM5, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM5>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        let Self { tim, clk } = self;
+        tim5(tim, freq.into(), clk)
+    }
+}
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+impl Timer<TIM8> {
+    /// Starts the counter at `freq` and configures the channels of `pins` as capture inputs
+    pub fn capture<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        freq: T,
+    ) -> InputCapture<TIM8, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM8>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        let Self { tim, clk } = self;
+        tim8(tim, freq.into(), clk)
+    }
+}
+
+macro_rules! hal {
+    ($($TIMX:ident: ($timX:ident),)+) => {
+        $(
+            fn $timX<REMAP, P, PINS>(
+                tim: $TIMX,
+                freq: Hertz,
+                clk: Hertz,
+            ) -> InputCapture<$TIMX, REMAP, P, PINS>
+            where
+                REMAP: Remap<Periph = $TIMX>,
+                PINS: Pins<REMAP, P>,
+            {
+                tim.cr1.modify(|_, w| w.cen().clear_bit());
+
+                // Map each channel to its own input
+                if PINS::C1 {
+                    tim.ccmr1_input().modify(|_, w| w.cc1s().ti1());
+                }
+                if PINS::C2 {
+                    tim.ccmr1_input().modify(|_, w| w.cc2s().ti2());
+                }
+                if PINS::C3 {
+                    tim.ccmr2_input().modify(|_, w| w.cc3s().ti3());
+                }
+                if PINS::C4 {
+                    tim.ccmr2_input().modify(|_, w| w.cc4s().ti4());
+                }
+
+                let psc = u16(clk.0 / freq.0 - 1).unwrap();
+                tim.psc.write(|w| w.psc().bits(psc));
+                // TODO: Remove this `allow` once this field is made safe for stm32f100
+                #[allow(unused_unsafe)]
+                tim.arr.write(|w| unsafe { w.arr().bits(u16::MAX) });
+
+                // The psc register is buffered, so we trigger an update event to update it
+                // Sets the URS bit to prevent an interrupt from being triggered by the UG bit
+                tim.cr1.modify(|_, w| w.urs().set_bit());
+                tim.egr.write(|w| w.ug().set_bit());
+                tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                InputCapture {
+                    clk: Hertz(clk.0 / (u32::from(psc) + 1)),
+                    _pins: PhantomData,
+                }
+            }
+
+            impl<REMAP, P, PINS> InputCapture<$TIMX, REMAP, P, PINS>
+            where
+                REMAP: Remap<Periph = $TIMX>,
+                PINS: Pins<REMAP, P>,
+            {
+                /// Sets the edge polarity, the prescaler and the filter of a channel
+                pub fn configure(&mut self, channel: Channel, config: Config) {
+                    let tim = unsafe { &*$TIMX::ptr() };
+                    let psc = config.prescaler as u8;
+                    let filter = config.filter;
+                    // TODO: Remove this `allow` once these fields are made safe for all devices
+                    #[allow(unused_unsafe)]
+                    match PINS::check_used(channel) {
+                        Channel::C1 => tim.ccmr1_input().modify(|_, w| unsafe {
+                            w.ic1psc().bits(psc).ic1f().bits(filter)
+                        }),
+                        Channel::C2 => tim.ccmr1_input().modify(|_, w| unsafe {
+                            w.ic2psc().bits(psc).ic2f().bits(filter)
+                        }),
+                        Channel::C3 => tim.ccmr2_input().modify(|_, w| unsafe {
+                            w.ic3psc().bits(psc).ic3f().bits(filter)
+                        }),
+                        Channel::C4 => tim.ccmr2_input().modify(|_, w| unsafe {
+                            w.ic4psc().bits(psc).ic4f().bits(filter)
+                        }),
+                    }
+                    let bit = match channel {
+                        Channel::C1 => 1,
+                        Channel::C2 => 5,
+                        Channel::C3 => 9,
+                        Channel::C4 => 13,
+                    };
+                    unsafe { bb::write(&tim.ccer, bit, config.polarity == Polarity::Falling) }
+                }
+
+                /// Retrieves the current counter value
+                pub fn cnt(&self) -> u16 {
+                    unsafe { (*$TIMX::ptr()).cnt.read().cnt().bits() }
+                }
+            }
+        )+
+    }
+}
+
+macro_rules! channel {
+    ($TIMX:ident, $C:ident, $ccr:ident, $ccif:ident, $ccof:ident, $bit:literal) => {
+        impl CaptureChannel<$TIMX, $C> {
+            /// Starts capturing
+            pub fn enable(&mut self) {
+                unsafe { bb::set(&(*$TIMX::ptr()).ccer, 4 * $bit - 4) }
+            }
+
+            /// Stops capturing
+            pub fn disable(&mut self) {
+                unsafe { bb::clear(&(*$TIMX::ptr()).ccer, 4 * $bit - 4) }
+            }
+
+            /// Changes the edge which triggers a capture, e.g. to capture both edges of a pulse
+            pub fn set_polarity(&mut self, polarity: Polarity) {
+                unsafe {
+                    bb::write(
+                        &(*$TIMX::ptr()).ccer,
+                        4 * $bit - 3,
+                        polarity == Polarity::Falling,
+                    )
+                }
+            }
+
+            /// Returns the last captured counter value, or `WouldBlock` if there was no capture
+            /// since the last call
+            pub fn capture(&mut self) -> nb::Result<u16, Error> {
+                let tim = unsafe { &*$TIMX::ptr() };
+                let sr = tim.sr.read();
+                if sr.$ccof().bit_is_set() {
+                    // Writing 1 has no effect on the other flags
+                    tim.sr
+                        .write(|w| unsafe { w.bits(0xffff & !(1 << ($bit + 8))) });
+                    // Reading the register clears the capture flag
+                    tim.$ccr.read();
+                    Err(nb::Error::Other(Error::Overcapture))
+                } else if sr.$ccif().bit_is_set() {
+                    Ok(tim.$ccr.read().ccr().bits())
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            }
+
+            /// Starts listening for the capture interrupt
+            pub fn listen(&mut self) {
+                unsafe { bb::set(&(*$TIMX::ptr()).dier, $bit) }
+            }
+
+            /// Stops listening for the capture interrupt
+            pub fn unlisten(&mut self) {
+                unsafe { bb::clear(&(*$TIMX::ptr()).dier, $bit) }
+            }
+        }
+    };
+}
+
+macro_rules! channel_dma {
+    ($TIMX:ident, $C:ident, $ccr:ident, $bit:literal, $dmach:ty, $capturedma:ident) => {
+        pub type $capturedma = RxDma<CaptureChannel<$TIMX, $C>, $dmach>;
+
+        impl Receive for $capturedma {
+            type RxChannel = $dmach;
+            type TransmittedWord = u16;
+        }
+
+        impl TransferPayload for $capturedma {
+            fn start(&mut self) {
+                self.channel.start();
+            }
+            fn stop(&mut self) {
+                self.channel.stop();
+            }
+        }
+
+        impl CaptureChannel<$TIMX, $C> {
+            pub fn with_dma(self, channel: $dmach) -> $capturedma {
+                // CCxDE
+                unsafe { bb::set(&(*$TIMX::ptr()).dier, $bit + 8) }
+                RxDma {
+                    payload: self,
+                    channel,
+                }
+            }
+        }
+
+        impl $capturedma {
+            pub fn release(mut self) -> (CaptureChannel<$TIMX, $C>, $dmach) {
+                self.stop();
+                unsafe { bb::clear(&(*$TIMX::ptr()).dier, $bit + 8) }
+                let RxDma { payload, channel } = self;
+                (payload, channel)
+            }
+
+            fn configure_channel(&mut self, ptr: *mut u16, len: usize, circular: bool) {
+                self.channel.set_peripheral_address(
+                    unsafe { &(*$TIMX::ptr()).$ccr as *const _ as u32 },
+                    false,
+                );
+                self.channel.set_memory_address(ptr as u32, true);
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.ch().cr.modify(|_, w| {
+                    w.mem2mem()
+                        .clear_bit()
+                        .pl()
+                        .medium()
+                        .msize()
+                        .bits16()
+                        .psize()
+                        .bits16()
+                        .circ()
+                        .bit(circular)
+                        .dir()
+                        .clear_bit()
+                });
+            }
+        }
+
+        impl<B> crate::dma::CircReadDma<B, u16> for $capturedma
+        where
+            &'static mut [B; 2]: StaticWriteBuffer<Word = u16>,
+            B: 'static,
+        {
+            fn circ_read(mut self, mut buffer: &'static mut [B; 2]) -> CircBuffer<B, Self> {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.static_write_buffer() };
+                self.configure_channel(ptr, len, true);
+                self.start();
+
+                CircBuffer::new(buffer, self)
+            }
+        }
+
+        impl<B> crate::dma::ReadDma<B, u16> for $capturedma
+        where
+            B: StaticWriteBuffer<Word = u16>,
+        {
+            fn read(mut self, mut buffer: B) -> Transfer<W, B, Self> {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.static_write_buffer() };
+                self.configure_channel(ptr, len, false);
+                self.start();
+
+                Transfer::w(buffer, self)
+            }
+        }
+    };
+}
+
+macro_rules! channels {
+    ($($TIMX:ident: [$($C:ident: ($ccr:ident, $ccif:ident, $ccof:ident, $bit:literal$(, $dmach:ty, $capturedma:ident)?),)+],)+) => {
+        $(
+            $(
+                channel!($TIMX, $C, $ccr, $ccif, $ccof, $bit);
+                $(
+                    channel_dma!($TIMX, $C, $ccr, $bit, $dmach, $capturedma);
+                )?
+            )+
+        )+
+    };
+}
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+hal! {
+    TIM1: (tim1),
+}
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+channels! {
+    TIM1: [
+        C1: (ccr1, cc1if, cc1of, 1, dma1::C2, Tim1Ch1CaptureDma),
+        C2: (ccr2, cc2if, cc2of, 2, dma1::C3, Tim1Ch2CaptureDma),
+        C3: (ccr3, cc3if, cc3of, 3, dma1::C6, Tim1Ch3CaptureDma),
+        C4: (ccr4, cc4if, cc4of, 4, dma1::C4, Tim1Ch4CaptureDma),
+    ],
+}
+
+hal! {
+    TIM2: (tim2),
+    TIM3: (tim3),
+}
+
+channels! {
+    TIM2: [
+        C1: (ccr1, cc1if, cc1of, 1, dma1::C5, Tim2Ch1CaptureDma),
+        C2: (ccr2, cc2if, cc2of, 2, dma1::C7, Tim2Ch2CaptureDma),
+        C3: (ccr3, cc3if, cc3of, 3, dma1::C1, Tim2Ch3CaptureDma),
+        C4: (ccr4, cc4if, cc4of, 4, dma1::C7, Tim2Ch4CaptureDma),
+    ],
+    TIM3: [
+        C1: (ccr1, cc1if, cc1of, 1, dma1::C6, Tim3Ch1CaptureDma),
+        C2: (ccr2, cc2if, cc2of, 2),
+        C3: (ccr3, cc3if, cc3of, 3, dma1::C2, Tim3Ch3CaptureDma),
+        C4: (ccr4, cc4if, cc4of, 4, dma1::C3, Tim3Ch4CaptureDma),
+    ],
+}
+
+#[cfg(feature = "medium")]
+hal! {
+    TIM4: (tim4),
+}
+
+#[cfg(feature = "medium")]
+channels! {
+    TIM4: [
+        C1: (ccr1, cc1if, cc1of, 1, dma1::C1, Tim4Ch1CaptureDma),
+        C2: (ccr2, cc2if, cc2of, 2, dma1::C4, Tim4Ch2CaptureDma),
+        C3: (ccr3, cc3if, cc3of, 3, dma1::C5, Tim4Ch3CaptureDma),
+        C4: (ccr4, cc4if, cc4of, 4),
+    ],
+}
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+hal! {
+    TIM5: (tim5),
+}
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+channels! {
+    TIM5: [
+        C1: (ccr1, cc1if, cc1of, 1, dma2::C5, Tim5Ch1CaptureDma),
+        C2: (ccr2, cc2if, cc2of, 2, dma2::C4, Tim5Ch2CaptureDma),
+        C3: (ccr3, cc3if, cc3of, 3, dma2::C2, Tim5Ch3CaptureDma),
+        C4: (ccr4, cc4if, cc4of, 4, dma2::C1, Tim5Ch4CaptureDma),
+    ],
+}
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+hal! {
+    TIM8: (tim8),
+}
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+channels! {
+    TIM8: [
+        C1: (ccr1, cc1if, cc1of, 1, dma2::C3, Tim8Ch1CaptureDma),
+        C2: (ccr2, cc2if, cc2of, 2, dma2::C5, Tim8Ch2CaptureDma),
+        C3: (ccr3, cc3if, cc3of, 3, dma2::C1, Tim8Ch3CaptureDma),
+        C4: (ccr4, cc4if, cc4of, 4, dma2::C2, Tim8Ch4CaptureDma),
+    ],
+}
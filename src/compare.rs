@@ -0,0 +1,752 @@
+/*!
+  # Output compare and one-pulse mode
+
+  ## Output compare
+
+  The timers `TIM1` to `TIM5` and `TIM8` compare their counter with the capture/compare register
+  of each channel. On a match, the output of the channel can be set active, set inactive or
+  toggled, and the CCx interrupt can be raised. The output can also be forced to a level
+  regardless of the counter.
+
+  The counter runs at the frequency passed to `compare` and wraps at the auto-reload value, which
+  is `u16::MAX` unless changed with [`OutputCompare::set_arr`].
+
+  ```rust
+  let pa0 = gpioa.pa0.into_alternate_push_pull(&mut gpioa.crl);
+
+  let compare = Timer::tim2(dp.TIM2, &clocks)
+      .compare::<Tim2NoRemap, _, _, _>(pa0, &mut afio.mapr, 1.mhz());
+  let now = compare.cnt();
+  let mut c1 = compare.split();
+  // Set PA0 high in 500 us
+  c1.set_compare(now.wrapping_add(500));
+  c1.set_mode(Mode::ActiveOnMatch);
+  c1.enable();
+  block!(c1.wait()).unwrap();
+  ```
+
+  ## One-pulse mode
+
+  In one-pulse mode, the counter stops after one period. Every enabled channel outputs a single
+  pulse which starts `delay` ticks after the counter has been started and lasts `width` ticks.
+  The counter is started either by software with [`OnePulse::trigger`], or by an edge on the
+  input of channel 1 or 2, which gives a delay that doesn't depend on interrupt latency.
+
+  ```rust
+  let output = gpioa.pa7.into_alternate_push_pull(&mut gpioa.crl);
+  let zero_cross = gpioa.pa6.into_floating_input(&mut gpioa.crl);
+
+  let mut pulse = Timer::tim3(dp.TIM3, &clocks)
+      .one_pulse::<Tim3NoRemap, _, _, _>(output, &mut afio.mapr, 1.mhz());
+  pulse.set_trigger(zero_cross, Polarity::Rising);
+  // Fire the triac 2 ms after the zero crossing with a 100 us pulse
+  pulse.set_pulse(2_000, 100);
+  pulse.enable(Channel::C2);
+  ```
+*/
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+use crate::pac::TIM1;
+#[cfg(feature = "medium")]
+use crate::pac::TIM4;
+#[cfg(any(feature = "high", feature = "connectivity"))]
+use crate::pac::TIM5;
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+use crate::pac::TIM8;
+use crate::pac::{TIM2, TIM3};
+use cast::u16;
+
+use crate::afio::MAPR;
+use crate::bb;
+use crate::gpio;
+use crate::time::Hertz;
+use crate::timer::sealed::{Ch1, Ch2, Remap};
+use crate::timer::Timer;
+
+pub use crate::capture::{InputMode, Polarity};
+use crate::pwm::ChannelWrapper;
+pub use crate::pwm::{Channel, Pins, C1, C2, C3, C4};
+
+/// Selects [`CompareChannel`] as the channels of [`Pins`]
+pub struct CompareChannels;
+
+impl<TIM, C> ChannelWrapper<TIM, C> for CompareChannels {
+    type Channel = CompareChannel<TIM, C>;
+
+    fn channel() -> Self::Channel {
+        CompareChannel {
+            _channel: PhantomData,
+            _tim: PhantomData,
+        }
+    }
+}
+
+/// Input pin which starts the counter in one-pulse mode, the input of channel 1 or 2
+pub trait TriggerPin<REMAP, C> {
+    const CHANNEL: Channel;
+}
+
+impl<REMAP, P> TriggerPin<REMAP, C1> for P
+where
+    P: Ch1<REMAP> + gpio::PinExt,
+    <P as gpio::PinExt>::Mode: InputMode,
+{
+    const CHANNEL: Channel = Channel::C1;
+}
+
+impl<REMAP, P> TriggerPin<REMAP, C2> for P
+where
+    P: Ch2<REMAP> + gpio::PinExt,
+    <P as gpio::PinExt>::Mode: InputMode,
+{
+    const CHANNEL: Channel = Channel::C2;
+}
+
+/// Output compare mode (OCxM)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// The output keeps its level on a match
+    Frozen = 0,
+    /// The output is set active on a match
+    ActiveOnMatch = 1,
+    /// The output is set inactive on a match
+    InactiveOnMatch = 2,
+    /// The output toggles on a match
+    Toggle = 3,
+    /// The output is inactive
+    ForceInactive = 4,
+    /// The output is active
+    ForceActive = 5,
+    /// The output is active while the counter is below the compare value
+    PwmMode1 = 6,
+    /// The output is active while the counter is at or above the compare value
+    PwmMode2 = 7,
+}
+
+/// Timer in output compare mode
+pub struct OutputCompare<TIM, REMAP, P, PINS>
+where
+    REMAP: Remap<Periph = TIM>,
+    PINS: Pins<REMAP, P, CompareChannels>,
+{
+    clk: Hertz,
+    _pins: PhantomData<(TIM, REMAP, P, PINS)>,
+}
+
+impl<TIM, REMAP, P, PINS> OutputCompare<TIM, REMAP, P, PINS>
+where
+    REMAP: Remap<Periph = TIM>,
+    PINS: Pins<REMAP, P, CompareChannels>,
+{
+    /// Returns the frequency the counter runs at
+    pub fn frequency(&self) -> Hertz {
+        self.clk
+    }
+
+    pub fn split(self) -> PINS::Channels {
+        PINS::split()
+    }
+}
+
+/// Compare channel of a timer
+pub struct CompareChannel<TIM, CHANNEL> {
+    _channel: PhantomData<CHANNEL>,
+    _tim: PhantomData<TIM>,
+}
+
+/// Timer in one-pulse mode
+pub struct OnePulse<TIM, REMAP, P, PINS>
+where
+    REMAP: Remap<Periph = TIM>,
+    PINS: Pins<REMAP, P, CompareChannels>,
+{
+    clk: Hertz,
+    _pins: PhantomData<(TIM, REMAP, P, PINS)>,
+}
+
+impl<TIM, REMAP, P, PINS> OnePulse<TIM, REMAP, P, PINS>
+where
+    REMAP: Remap<Periph = TIM>,
+    PINS: Pins<REMAP, P, CompareChannels>,
+{
+    /// Returns the frequency the counter runs at
+    pub fn frequency(&self) -> Hertz {
+        self.clk
+    }
+}
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+impl Timer<TIM1> {
+    /// Starts the counter at `freq` with the channels of `pins` in output compare mode
+    pub fn compare<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> OutputCompare<TIM1, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM1>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| unsafe { w.tim1_remap().bits(REMAP::REMAP) });
+        // The outputs of TIM1 are only driven with the main output enable bit set
+        self.tim.bdtr.modify(|_, w| w.moe().set_bit());
+
+        let Self { tim, clk } = self;
+        OutputCompare {
+            clk: tim1::<REMAP, P, PINS>(&tim, freq.into(), clk, false),
+            _pins: PhantomData,
+        }
+    }
+
+    /// Sets up the counter at `freq` to output single pulses on the channels of `pins`
+    pub fn one_pulse<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> OnePulse<TIM1, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM1>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| unsafe { w.tim1_remap().bits(REMAP::REMAP) });
+        self.tim.bdtr.modify(|_, w| w.moe().set_bit());
+
+        let Self { tim, clk } = self;
+        OnePulse {
+            clk: tim1::<REMAP, P, PINS>(&tim, freq.into(), clk, true),
+            _pins: PhantomData,
+        }
+    }
+}
+
+impl Timer<TIM2> {
+    /// Starts the counter at `freq` with the channels of `pins` in output compare mode
+    pub fn compare<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> OutputCompare<TIM2, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM2>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| unsafe { w.tim2_remap().bits(REMAP::REMAP) });
+
+        let Self { tim, clk } = self;
+        OutputCompare {
+            clk: tim2::<REMAP, P, PINS>(&tim, freq.into(), clk, false),
+            _pins: PhantomData,
+        }
+    }
+
+    /// Sets up the counter at `freq` to output single pulses on the channels of `pins`
+    pub fn one_pulse<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> OnePulse<TIM2, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM2>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| unsafe { w.tim2_remap().bits(REMAP::REMAP) });
+
+        let Self { tim, clk } = self;
+        OnePulse {
+            clk: tim2::<REMAP, P, PINS>(&tim, freq.into(), clk, true),
+            _pins: PhantomData,
+        }
+    }
+}
+
+impl Timer<TIM3> {
+    /// Starts the counter at `freq` with the channels of `pins` in output compare mode
+    pub fn compare<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> OutputCompare<TIM3, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM3>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| unsafe { w.tim3_remap().bits(REMAP::REMAP) });
+
+        let Self { tim, clk } = self;
+        OutputCompare {
+            clk: tim3::<REMAP, P, PINS>(&tim, freq.into(), clk, false),
+            _pins: PhantomData,
+        }
+    }
+
+    /// Sets up the counter at `freq` to output single pulses on the channels of `pins`
+    pub fn one_pulse<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> OnePulse<TIM3, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM3>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| unsafe { w.tim3_remap().bits(REMAP::REMAP) });
+
+        let Self { tim, clk } = self;
+        OnePulse {
+            clk: tim3::<REMAP, P, PINS>(&tim, freq.into(), clk, true),
+            _pins: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "medium")]
+impl Timer<TIM4> {
+    /// Starts the counter at `freq` with the channels of `pins` in output compare mode
+    pub fn compare<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> OutputCompare<TIM4, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM4>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| w.tim4_remap().bit(REMAP::REMAP == 1));
+
+        let Self { tim, clk } = self;
+        OutputCompare {
+            clk: tim4::<REMAP, P, PINS>(&tim, freq.into(), clk, false),
+            _pins: PhantomData,
+        }
+    }
+
+    /// Sets up the counter at `freq` to output single pulses on the channels of `pins`
+    pub fn one_pulse<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr: &mut MAPR,
+        freq: T,
+    ) -> OnePulse<TIM4, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM4>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        mapr.modify_mapr(|_, w| w.tim4_remap().bit(REMAP::REMAP == 1));
+
+        let Self { tim, clk } = self;
+        OnePulse {
+            clk: tim4::<REMAP, P, PINS>(&tim, freq.into(), clk, true),
+            _pins: PhantomData,
+        }
+    }
+}
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+impl Timer<TIM5> {
+    /// Starts the counter at `freq` with the channels of `pins` in output compare mode
+    pub fn compare<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        freq: T,
+    ) -> OutputCompare<TIM5, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM5>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        let Self { tim, clk } = self;
+        OutputCompare {
+            clk: tim5::<REMAP, P, PINS>(&tim, freq.into(), clk, false),
+            _pins: PhantomData,
+        }
+    }
+
+    /// Sets up the counter at `freq` to output single pulses on the channels of `pins`
+    pub fn one_pulse<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        freq: T,
+    ) -> OnePulse<TIM5, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM5>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        let Self { tim, clk } = self;
+        OnePulse {
+            clk: tim5::<REMAP, P, PINS>(&tim, freq.into(), clk, true),
+            _pins: PhantomData,
+        }
+    }
+}
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+impl Timer<TIM8> {
+    /// Starts the counter at `freq` with the channels of `pins` in output compare mode
+    pub fn compare<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        freq: T,
+    ) -> OutputCompare<TIM8, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM8>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        // The outputs of TIM8 are only driven with the main output enable bit set
+        self.tim.bdtr.modify(|_, w| w.moe().set_bit());
+
+        let Self { tim, clk } = self;
+        OutputCompare {
+            clk: tim8::<REMAP, P, PINS>(&tim, freq.into(), clk, false),
+            _pins: PhantomData,
+        }
+    }
+
+    /// Sets up the counter at `freq` to output single pulses on the channels of `pins`
+    pub fn one_pulse<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        freq: T,
+    ) -> OnePulse<TIM8, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM8>,
+        PINS: Pins<REMAP, P, CompareChannels>,
+        T: Into<Hertz>,
+    {
+        self.tim.bdtr.modify(|_, w| w.moe().set_bit());
+
+        let Self { tim, clk } = self;
+        OnePulse {
+            clk: tim8::<REMAP, P, PINS>(&tim, freq.into(), clk, true),
+            _pins: PhantomData,
+        }
+    }
+}
+
+macro_rules! hal {
+    ($($TIMX:ident: ($timX:ident),)+) => {
+        $(
+            /// Configures the channels and the counter, returns the counter frequency
+            fn $timX<REMAP, P, PINS>(
+                tim: &$TIMX,
+                freq: Hertz,
+                clk: Hertz,
+                one_pulse: bool,
+            ) -> Hertz
+            where
+                REMAP: Remap<Periph = $TIMX>,
+                PINS: Pins<REMAP, P, CompareChannels>,
+            {
+                tim.cr1.modify(|_, w| w.cen().clear_bit());
+
+                // In one-pulse mode, the pulse lasts from the compare value to the end of the
+                // period. In output compare mode, a new compare value is used immediately.
+                let (mode, preload) = if one_pulse {
+                    (Mode::PwmMode2 as u8, true)
+                } else {
+                    (Mode::Frozen as u8, false)
+                };
+                if PINS::C1 {
+                    tim.ccmr1_output()
+                        .modify(|_, w| w.oc1pe().bit(preload).oc1m().bits(mode));
+                }
+                if PINS::C2 {
+                    tim.ccmr1_output()
+                        .modify(|_, w| w.oc2pe().bit(preload).oc2m().bits(mode));
+                }
+                if PINS::C3 {
+                    tim.ccmr2_output()
+                        .modify(|_, w| w.oc3pe().bit(preload).oc3m().bits(mode));
+                }
+                if PINS::C4 {
+                    tim.ccmr2_output()
+                        .modify(|_, w| w.oc4pe().bit(preload).oc4m().bits(mode));
+                }
+
+                let psc = u16(clk.0 / freq.0 - 1).unwrap();
+                tim.psc.write(|w| w.psc().bits(psc));
+                // TODO: Remove this `allow` once this field is made safe for stm32f100
+                #[allow(unused_unsafe)]
+                tim.arr.write(|w| unsafe { w.arr().bits(u16::MAX) });
+
+                // The psc register is buffered, so we trigger an update event to update it
+                // Sets the URS bit to prevent an interrupt from being triggered by the UG bit
+                tim.cr1.modify(|_, w| w.urs().set_bit());
+                tim.egr.write(|w| w.ug().set_bit());
+                tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                // The one-pulse counter is started by a trigger
+                tim.cr1.modify(|_, w| w.opm().bit(one_pulse).cen().bit(!one_pulse));
+
+                Hertz(clk.0 / (u32::from(psc) + 1))
+            }
+
+            impl<REMAP, P, PINS> OutputCompare<$TIMX, REMAP, P, PINS>
+            where
+                REMAP: Remap<Periph = $TIMX>,
+                PINS: Pins<REMAP, P, CompareChannels>,
+            {
+                /// Retrieves the current counter value
+                pub fn cnt(&self) -> u16 {
+                    unsafe { (*$TIMX::ptr()).cnt.read().cnt().bits() }
+                }
+
+                /// Retrieves the auto-reload value, the counter wraps after reaching it
+                pub fn arr(&self) -> u16 {
+                    unsafe { (*$TIMX::ptr()).arr.read().arr().bits() }
+                }
+
+                /// Sets the auto-reload value, e.g. to toggle an output at a fixed frequency
+                pub fn set_arr(&mut self, arr: u16) {
+                    // TODO: Remove this `allow` once this field is made safe for stm32f100
+                    #[allow(unused_unsafe)]
+                    unsafe {
+                        (*$TIMX::ptr()).arr.write(|w| w.arr().bits(arr))
+                    }
+                }
+            }
+
+            impl<REMAP, P, PINS> OnePulse<$TIMX, REMAP, P, PINS>
+            where
+                REMAP: Remap<Periph = $TIMX>,
+                PINS: Pins<REMAP, P, CompareChannels>,
+            {
+                /// Starts the counter on an edge of `pin`, the input of channel 1 or 2, which
+                /// must not be one of the outputs
+                pub fn set_trigger<C, TP>(&mut self, _pin: TP, polarity: Polarity)
+                where
+                    TP: TriggerPin<REMAP, C>,
+                {
+                    let tim = unsafe { &*$TIMX::ptr() };
+                    let falling = polarity == Polarity::Falling;
+                    match TP::CHANNEL {
+                        Channel::C1 => {
+                            assert!(!PINS::C1);
+                            tim.ccmr1_input().modify(|_, w| w.cc1s().ti1());
+                            unsafe { bb::write(&tim.ccer, 1, falling) }
+                            tim.smcr.modify(|_, w| w.ts().ti1fp1().sms().trigger_mode());
+                        }
+                        _ => {
+                            assert!(!PINS::C2);
+                            tim.ccmr1_input().modify(|_, w| w.cc2s().ti2());
+                            unsafe { bb::write(&tim.ccer, 5, falling) }
+                            tim.smcr.modify(|_, w| w.ts().ti2fp2().sms().trigger_mode());
+                        }
+                    }
+                }
+
+                /// Sets the pulse of all the channels to start `delay` ticks after the trigger
+                /// and to last `width` ticks
+                ///
+                /// Both `delay` and `width` must be at least 1, and `delay + width - 1` must fit
+                /// in 16 bits.
+                pub fn set_pulse(&mut self, delay: u16, width: u16) {
+                    // With a compare value of 0, the output is also active while the stopped
+                    // counter waits at 0
+                    assert!(delay > 0);
+                    assert!(width > 0);
+                    let arr = u16(u32::from(delay) + u32::from(width) - 1).unwrap();
+                    let tim = unsafe { &*$TIMX::ptr() };
+                    if PINS::C1 {
+                        tim.ccr1.write(|w| w.ccr().bits(delay));
+                    }
+                    if PINS::C2 {
+                        tim.ccr2.write(|w| w.ccr().bits(delay));
+                    }
+                    if PINS::C3 {
+                        tim.ccr3.write(|w| w.ccr().bits(delay));
+                    }
+                    if PINS::C4 {
+                        tim.ccr4.write(|w| w.ccr().bits(delay));
+                    }
+                    // TODO: Remove this `allow` once this field is made safe for stm32f100
+                    #[allow(unused_unsafe)]
+                    tim.arr.write(|w| unsafe { w.arr().bits(arr) });
+
+                    // Loads the preloaded compare values unless a pulse is running
+                    if tim.cr1.read().cen().bit_is_clear() {
+                        tim.cr1.modify(|_, w| w.urs().set_bit());
+                        tim.egr.write(|w| w.ug().set_bit());
+                        tim.cr1.modify(|_, w| w.urs().clear_bit());
+                    }
+                }
+
+                pub fn enable(&mut self, channel: Channel) {
+                    match PINS::check_used(channel) {
+                        Channel::C1 => unsafe { bb::set(&(*$TIMX::ptr()).ccer, 0) },
+                        Channel::C2 => unsafe { bb::set(&(*$TIMX::ptr()).ccer, 4) },
+                        Channel::C3 => unsafe { bb::set(&(*$TIMX::ptr()).ccer, 8) },
+                        Channel::C4 => unsafe { bb::set(&(*$TIMX::ptr()).ccer, 12) },
+                    }
+                }
+
+                pub fn disable(&mut self, channel: Channel) {
+                    match PINS::check_used(channel) {
+                        Channel::C1 => unsafe { bb::clear(&(*$TIMX::ptr()).ccer, 0) },
+                        Channel::C2 => unsafe { bb::clear(&(*$TIMX::ptr()).ccer, 4) },
+                        Channel::C3 => unsafe { bb::clear(&(*$TIMX::ptr()).ccer, 8) },
+                        Channel::C4 => unsafe { bb::clear(&(*$TIMX::ptr()).ccer, 12) },
+                    }
+                }
+
+                /// Starts a pulse by software
+                pub fn trigger(&mut self) {
+                    let tim = unsafe { &*$TIMX::ptr() };
+                    tim.sr.write(|w| unsafe { w.bits(0xffff & !1) });
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+                }
+
+                /// Returns `true` while a pulse is being output
+                pub fn is_running(&self) -> bool {
+                    unsafe { (*$TIMX::ptr()).cr1.read().cen().bit_is_set() }
+                }
+
+                /// Waits for the end of the pulse
+                pub fn wait(&mut self) -> nb::Result<(), Infallible> {
+                    let tim = unsafe { &*$TIMX::ptr() };
+                    if tim.sr.read().uif().bit_is_set() {
+                        tim.sr.write(|w| unsafe { w.bits(0xffff & !1) });
+                        Ok(())
+                    } else {
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+
+                /// Starts listening for the end of the pulse interrupt
+                pub fn listen(&mut self) {
+                    unsafe { bb::set(&(*$TIMX::ptr()).dier, 0) }
+                }
+
+                /// Stops listening for the end of the pulse interrupt
+                pub fn unlisten(&mut self) {
+                    unsafe { bb::clear(&(*$TIMX::ptr()).dier, 0) }
+                }
+            }
+        )+
+    }
+}
+
+macro_rules! channel {
+    ($TIMX:ident, $C:ident, $ccmr_output:ident, $ocm:ident, $ccr:ident, $ccif:ident, $bit:literal) => {
+        impl CompareChannel<$TIMX, $C> {
+            /// Sets what happens to the output when the counter matches the compare value
+            pub fn set_mode(&mut self, mode: Mode) {
+                unsafe {
+                    (*$TIMX::ptr())
+                        .$ccmr_output()
+                        .modify(|_, w| w.$ocm().bits(mode as u8))
+                }
+            }
+
+            /// Sets the compare value, which is used from the next counter tick on
+            pub fn set_compare(&mut self, value: u16) {
+                unsafe { (*$TIMX::ptr()).$ccr.write(|w| w.ccr().bits(value)) }
+            }
+
+            pub fn get_compare(&self) -> u16 {
+                unsafe { (*$TIMX::ptr()).$ccr.read().ccr().bits() }
+            }
+
+            /// Connects the output to the pin
+            pub fn enable(&mut self) {
+                unsafe { bb::set(&(*$TIMX::ptr()).ccer, 4 * $bit - 4) }
+            }
+
+            /// Disconnects the output from the pin
+            pub fn disable(&mut self) {
+                unsafe { bb::clear(&(*$TIMX::ptr()).ccer, 4 * $bit - 4) }
+            }
+
+            /// Waits for the counter to match the compare value
+            pub fn wait(&mut self) -> nb::Result<(), Infallible> {
+                let tim = unsafe { &*$TIMX::ptr() };
+                if tim.sr.read().$ccif().bit_is_set() {
+                    // Writing 1 has no effect on the other flags
+                    tim.sr.write(|w| unsafe { w.bits(0xffff & !(1 << $bit)) });
+                    Ok(())
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            }
+
+            /// Starts listening for the compare match interrupt
+            pub fn listen(&mut self) {
+                unsafe { bb::set(&(*$TIMX::ptr()).dier, $bit) }
+            }
+
+            /// Stops listening for the compare match interrupt
+            pub fn unlisten(&mut self) {
+                unsafe { bb::clear(&(*$TIMX::ptr()).dier, $bit) }
+            }
+        }
+    };
+}
+
+macro_rules! channels {
+    ($($TIMX:ident,)+) => {
+        $(
+            channel!($TIMX, C1, ccmr1_output, oc1m, ccr1, cc1if, 1);
+            channel!($TIMX, C2, ccmr1_output, oc2m, ccr2, cc2if, 2);
+            channel!($TIMX, C3, ccmr2_output, oc3m, ccr3, cc3if, 3);
+            channel!($TIMX, C4, ccmr2_output, oc4m, ccr4, cc4if, 4);
+        )+
+    };
+}
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+hal! {
+    TIM1: (tim1),
+}
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+channels!(TIM1,);
+
+hal! {
+    TIM2: (tim2),
+    TIM3: (tim3),
+}
+
+channels!(TIM2, TIM3,);
+
+#[cfg(feature = "medium")]
+hal! {
+    TIM4: (tim4),
+}
+
+#[cfg(feature = "medium")]
+channels!(TIM4,);
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+hal! {
+    TIM5: (tim5),
+}
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+channels!(TIM5,);
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+hal! {
+    TIM8: (tim8),
+}
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+channels!(TIM8,);
@@ -293,6 +293,80 @@ macro_rules! dma {
                         }
                     }
 
+                    impl<B, PAYLOAD> CircBuffer<B, TxDma<PAYLOAD, $CX>>
+                    where
+                        TxDma<PAYLOAD, $CX>: TransferPayload,
+                    {
+                        /// Fills the writable half of the buffer, which the DMA has finished sending
+                        pub fn poke<R, F>(&mut self, f: F) -> Result<R, Error>
+                            where
+                            F: FnOnce(&mut B, Half) -> R,
+                        {
+                            let half_being_written = self.writable_half()?;
+
+                            let buf = match half_being_written {
+                                Half::First => &mut self.buffer[0],
+                                Half::Second => &mut self.buffer[1],
+                            };
+
+                            let ret = f(buf, half_being_written);
+
+                            let isr = self.payload.channel.isr();
+                            let first_half_is_done = isr.$htifX().bit_is_set();
+                            let second_half_is_done = isr.$tcifX().bit_is_set();
+
+                            if (half_being_written == Half::First && second_half_is_done) ||
+                                (half_being_written == Half::Second && first_half_is_done) {
+                                Err(Error::Overrun)
+                            } else {
+                                Ok(ret)
+                            }
+                        }
+
+                        /// Returns the `Half` of the buffer that can be written
+                        pub fn writable_half(&mut self) -> Result<Half, Error> {
+                            let isr = self.payload.channel.isr();
+                            let first_half_is_done = isr.$htifX().bit_is_set();
+                            let second_half_is_done = isr.$tcifX().bit_is_set();
+
+                            if first_half_is_done && second_half_is_done {
+                                return Err(Error::Overrun);
+                            }
+
+                            let last_written_half = self.readable_half;
+
+                            Ok(match last_written_half {
+                                Half::First => {
+                                    if second_half_is_done {
+                                        self.payload.channel.ifcr().write(|w| w.$ctcifX().set_bit());
+
+                                        self.readable_half = Half::Second;
+                                        Half::Second
+                                    } else {
+                                        last_written_half
+                                    }
+                                }
+                                Half::Second => {
+                                    if first_half_is_done {
+                                        self.payload.channel.ifcr().write(|w| w.$chtifX().set_bit());
+
+                                        self.readable_half = Half::First;
+                                        Half::First
+                                    } else {
+                                        last_written_half
+                                    }
+                                }
+                            })
+                        }
+
+                        /// Stops the transfer and returns the underlying buffer and TxDma
+                        pub fn stop(mut self) -> (&'static mut [B; 2], TxDma<PAYLOAD, $CX>) {
+                            self.payload.stop();
+
+                            (self.buffer, self.payload)
+                        }
+                    }
+
                     impl<BUFFER, PAYLOAD, MODE> Transfer<MODE, BUFFER, RxDma<PAYLOAD, $CX>>
                     where
                         RxDma<PAYLOAD, $CX>: TransferPayload,
@@ -570,6 +644,16 @@ where
     fn circ_read(self, buffer: &'static mut [B; 2]) -> CircBuffer<B, Self>;
 }
 
+/// Trait for circular DMA writing from memory to peripheral.
+pub trait CircWriteDma<B, TS>: Transmit
+where
+    &'static mut [B; 2]: StaticWriteBuffer<Word = TS>,
+    B: 'static,
+    Self: core::marker::Sized,
+{
+    fn circ_write(self, buffer: &'static mut [B; 2]) -> CircBuffer<B, Self>;
+}
+
 /// Trait for DMA readings from peripheral to memory.
 pub trait ReadDma<B, RS>: Receive
 where
@@ -651,6 +651,18 @@ impl<CR, const P: char, const N: u8> Pin<Output<OpenDrain>, CR, P, N> {
     }
 }
 
+impl<CR, const P: char, const N: u8> Pin<Alternate<OpenDrain>, CR, P, N> {
+    /// Reads the level of the line, which the input buffer samples also in alternate mode
+    #[inline]
+    pub fn is_high(&self) -> bool {
+        !self._is_low()
+    }
+    #[inline]
+    pub fn is_low(&self) -> bool {
+        self._is_low()
+    }
+}
+
 impl<CR, const P: char, const N: u8> InputPin for Pin<Output<OpenDrain>, CR, P, N> {
     type Error = Infallible;
     #[inline]
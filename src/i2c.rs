@@ -6,17 +6,25 @@
 
 use crate::afio::MAPR;
 use crate::gpio::gpiob::{PB10, PB11, PB6, PB7, PB8, PB9};
-use crate::gpio::{Alternate, OpenDrain};
+use crate::gpio::{Alternate, Cr, OpenDrain, PinState, CRH, CRL};
 use crate::hal::blocking::i2c::{Read, Write, WriteRead};
 use crate::pac::{DWT, I2C1, I2C2, RCC};
 use crate::rcc::{Clocks, Enable, GetBusFreq, Reset};
 use crate::time::Hertz;
 use core::ops::Deref;
+use cortex_m::asm::delay;
 use nb::Error::{Other, WouldBlock};
 use nb::{Error as NbError, Result as NbResult};
 
 pub mod blocking;
 pub use blocking::BlockingI2c;
+pub mod dma;
+pub mod interrupt;
+pub use interrupt::InterruptI2c;
+pub mod slave;
+pub use slave::I2cSlave;
+pub mod smbus;
+pub use smbus::SmBus;
 
 /// I2C error
 #[derive(Debug, Eq, PartialEq)]
@@ -30,9 +38,21 @@ pub enum Error {
     Acknowledge,
     /// Overrun/underrun
     Overrun,
-    // Pec, // SMBUS mode only
-    // Timeout, // SMBUS mode only
-    // Alert, // SMBUS mode only
+    /// Packet error code mismatch (SMBus mode only)
+    Pec,
+    /// Clock held low for too long (SMBus mode only)
+    Timeout,
+    /// SMBA pulled low by a device (SMBus mode only)
+    Alert,
+}
+
+/// I2C device address
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Address {
+    /// 7-bit address
+    SevenBit(u8),
+    /// 10-bit address
+    TenBit(u16),
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -108,6 +128,55 @@ impl Pins<I2C2> for (PB10<Alternate<OpenDrain>>, PB11<Alternate<OpenDrain>>) {
     const REMAP: bool = false;
 }
 
+/// I2C pins which can be driven as GPIO to free a stuck bus, see [`I2c::recover_bus`]
+pub trait RecoverPins {
+    /// Configuration register of the pins
+    type Cr;
+
+    /// Clocks SCL until the slave releases SDA, then generates a start and a stop condition.
+    /// Returns whether SDA is released afterwards.
+    fn recover(&mut self, cr: &mut Self::Cr) -> bool;
+}
+
+/// Half of a 100 kHz clock period at the highest system clock, longer at lower clocks
+const RECOVER_HALF_PERIOD: u32 = 360;
+
+macro_rules! recover_pins {
+    ($SCL:ident, $SDA:ident, $CR:ident) => {
+        impl RecoverPins for ($SCL<Alternate<OpenDrain>>, $SDA<Alternate<OpenDrain>>) {
+            type Cr = Cr<$CR, 'B'>;
+
+            fn recover(&mut self, cr: &mut Self::Cr) -> bool {
+                let (scl, sda) = self;
+                // A slave holding SDA low is in the middle of sending a byte, at most 9 clock
+                // pulses make it finish the byte and see a missing acknowledge
+                scl.as_open_drain_output_with_state(cr, PinState::High, |scl| {
+                    for _ in 0..9 {
+                        if sda.is_high() {
+                            break;
+                        }
+                        scl.set_low();
+                        delay(RECOVER_HALF_PERIOD);
+                        scl.set_high();
+                        delay(RECOVER_HALF_PERIOD);
+                    }
+                });
+                // SCL is released by the disabled peripheral, pulling SDA low and releasing it
+                // again is a start followed by a stop condition
+                sda.as_open_drain_output_with_state(cr, PinState::Low, |_| {
+                    delay(RECOVER_HALF_PERIOD)
+                });
+                delay(RECOVER_HALF_PERIOD);
+                sda.is_high()
+            }
+        }
+    };
+}
+
+recover_pins!(PB6, PB7, CRL);
+recover_pins!(PB8, PB9, CRH);
+recover_pins!(PB10, PB11, CRH);
+
 /// I2C peripheral operating in master mode
 pub struct I2c<I2C, PINS> {
     i2c: I2C,
@@ -259,3 +328,27 @@ where
         self.release()
     }
 }
+
+impl<I2C, PINS> I2c<I2C, PINS>
+where
+    I2C: Instance,
+    PINS: RecoverPins,
+{
+    /// Frees the bus when a slave holds SDA low, e.g. after a reset in the middle of a transfer
+    ///
+    /// SCL and SDA are temporarily switched to GPIO to clock out the slave and to generate a stop
+    /// condition. The peripheral is then reset, which also clears the BUSY flag when it is locked
+    /// by the analog filter ("Analog filter may provide wrong value, locking BUSY flag" in the
+    /// errata sheet). `Error::Bus` is returned if the bus is still not free.
+    pub fn recover_bus(&mut self, cr: &mut PINS::Cr) -> Result<(), Error> {
+        self.i2c.cr1.modify(|_, w| w.pe().clear_bit());
+        let released = self.pins.recover(cr);
+        self.reset();
+
+        if released && self.i2c.sr2.read().busy().bit_is_clear() {
+            Ok(())
+        } else {
+            Err(Error::Bus)
+        }
+    }
+}
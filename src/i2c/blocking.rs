@@ -1,4 +1,5 @@
 use super::*;
+use crate::hal::blocking::i2c::{Operation, Transactional};
 
 /// embedded-hal compatible blocking I2C implementation
 ///
@@ -236,69 +237,98 @@ where
         ret
     }
 
-    fn write_bytes_and_wait(&mut self, bytes: &[u8]) -> NbResult<(), Error> {
+    /// Sends the header of a 10-bit address, `11110` followed by the two high address bits
+    fn send_header_and_wait(&mut self, addr: u16, read: bool) -> NbResult<(), Error> {
         self.nb.i2c.sr1.read();
-        self.nb.i2c.sr2.read();
-
-        self.nb.i2c.dr.write(|w| w.dr().bits(bytes[0]));
-
-        for byte in &bytes[1..] {
-            busy_wait_cycles!(wait_for_flag!(self.nb.i2c, tx_e), self.timeouts.data)?;
-            self.nb.i2c.dr.write(|w| w.dr().bits(*byte));
-        }
-        busy_wait_cycles!(wait_for_flag!(self.nb.i2c, btf), self.timeouts.data)?;
-
-        Ok(())
-    }
-
-    fn write_without_stop(&mut self, addr: u8, bytes: &[u8]) -> NbResult<(), Error> {
-        self.send_start_and_wait()?;
-        self.send_addr_and_wait(addr, false)?;
-
-        let ret = self.write_bytes_and_wait(bytes);
+        let header = 0b1111_0000 | ((addr >> 7) as u8 & 0b110) | (if read { 1 } else { 0 });
+        self.nb.i2c.dr.write(|w| w.dr().bits(header));
+        let ret = if read {
+            busy_wait_cycles!(wait_for_flag!(self.nb.i2c, addr), self.timeouts.addr)
+        } else {
+            busy_wait_cycles!(wait_for_flag!(self.nb.i2c, add10), self.timeouts.addr)
+        };
         if ret == Err(Other(Error::Acknowledge)) {
             self.nb.send_stop();
         }
         ret
     }
-}
 
-impl<I2C, PINS> Write for BlockingI2c<I2C, PINS>
-where
-    I2C: Instance,
-{
-    type Error = NbError<Error>;
+    /// Sends the address after the start condition and waits until it is acknowledged. ADDR is
+    /// left set.
+    fn send_address_and_wait(&mut self, address: Address, read: bool) -> NbResult<(), Error> {
+        match address {
+            Address::SevenBit(addr) => self.send_addr_and_wait(addr, read),
+            Address::TenBit(addr) => {
+                self.send_header_and_wait(addr, false)?;
+                self.nb.i2c.sr1.read();
+                self.nb.i2c.dr.write(|w| w.dr().bits(addr as u8));
+                let ret = busy_wait_cycles!(wait_for_flag!(self.nb.i2c, addr), self.timeouts.addr);
+                if ret == Err(Other(Error::Acknowledge)) {
+                    self.nb.send_stop();
+                }
+                ret?;
+
+                if read {
+                    // A read is started with a repeated start and the header only
+                    self.nb.i2c.sr1.read();
+                    self.nb.i2c.sr2.read();
+                    self.send_start_and_wait()?;
+                    self.send_header_and_wait(addr, true)?;
+                }
+                Ok(())
+            }
+        }
+    }
 
-    fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Self::Error> {
-        self.write_without_stop(addr, bytes)?;
-        self.nb.send_stop();
-        busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+    fn write_bytes_and_wait(&mut self, mut bytes: impl Iterator<Item = u8>) -> NbResult<(), Error> {
+        self.nb.i2c.sr1.read();
+        self.nb.i2c.sr2.read();
+
+        if let Some(byte) = bytes.next() {
+            self.nb.i2c.dr.write(|w| w.dr().bits(byte));
+
+            for byte in bytes {
+                busy_wait_cycles!(wait_for_flag!(self.nb.i2c, tx_e), self.timeouts.data)?;
+                self.nb.i2c.dr.write(|w| w.dr().bits(byte));
+            }
+            busy_wait_cycles!(wait_for_flag!(self.nb.i2c, btf), self.timeouts.data)?;
+        }
 
         Ok(())
     }
-}
 
-impl<I2C, PINS> Read for BlockingI2c<I2C, PINS>
-where
-    I2C: Instance,
-{
-    type Error = NbError<Error>;
+    /// Generates the STOP condition at the end of a transaction, or a repeated START before the
+    /// next operation
+    fn send_stop_or_start(&mut self, stop: bool) {
+        if stop {
+            self.nb.send_stop();
+        } else {
+            self.nb.send_start();
+        }
+    }
 
-    fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
-        self.send_start_and_wait()?;
-        self.send_addr_and_wait(addr, true)?;
+    /// Reads `len` bytes into `buffer` after the address has been acknowledged, ADDR is still set
+    fn read_bytes_and_wait<'b>(
+        &mut self,
+        len: usize,
+        mut buffer: impl Iterator<Item = &'b mut u8>,
+        stop: bool,
+    ) -> NbResult<(), Error> {
+        let mut next = || buffer.next().unwrap();
 
-        match buffer.len() {
+        match len {
             1 => {
                 self.nb.i2c.cr1.modify(|_, w| w.ack().clear_bit());
                 self.nb.i2c.sr1.read();
                 self.nb.i2c.sr2.read();
-                self.nb.send_stop();
+                self.send_stop_or_start(stop);
 
                 busy_wait_cycles!(wait_for_flag!(self.nb.i2c, rx_ne), self.timeouts.data)?;
-                buffer[0] = self.nb.i2c.dr.read().dr().bits();
+                *next() = self.nb.i2c.dr.read().dr().bits();
 
-                busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+                if stop {
+                    busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+                }
                 self.nb.i2c.cr1.modify(|_, w| w.ack().set_bit());
             }
             2 => {
@@ -311,11 +341,13 @@ where
                 self.nb.i2c.cr1.modify(|_, w| w.ack().clear_bit());
 
                 busy_wait_cycles!(wait_for_flag!(self.nb.i2c, btf), self.timeouts.data)?;
-                self.nb.send_stop();
-                buffer[0] = self.nb.i2c.dr.read().dr().bits();
-                buffer[1] = self.nb.i2c.dr.read().dr().bits();
+                self.send_stop_or_start(stop);
+                *next() = self.nb.i2c.dr.read().dr().bits();
+                *next() = self.nb.i2c.dr.read().dr().bits();
 
-                busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+                if stop {
+                    busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+                }
                 self.nb
                     .i2c
                     .cr1
@@ -327,27 +359,146 @@ where
                 self.nb.i2c.sr1.read();
                 self.nb.i2c.sr2.read();
 
-                let (first_bytes, last_two_bytes) = buffer.split_at_mut(buffer_len - 3);
-                for byte in first_bytes {
+                for _ in 0..buffer_len - 3 {
                     busy_wait_cycles!(wait_for_flag!(self.nb.i2c, rx_ne), self.timeouts.data)?;
-                    *byte = self.nb.i2c.dr.read().dr().bits();
+                    *next() = self.nb.i2c.dr.read().dr().bits();
                 }
 
                 busy_wait_cycles!(wait_for_flag!(self.nb.i2c, btf), self.timeouts.data)?;
                 self.nb.i2c.cr1.modify(|_, w| w.ack().clear_bit());
-                last_two_bytes[0] = self.nb.i2c.dr.read().dr().bits();
-                self.nb.send_stop();
-                last_two_bytes[1] = self.nb.i2c.dr.read().dr().bits();
+                *next() = self.nb.i2c.dr.read().dr().bits();
+                self.send_stop_or_start(stop);
+                *next() = self.nb.i2c.dr.read().dr().bits();
                 busy_wait_cycles!(wait_for_flag!(self.nb.i2c, rx_ne), self.timeouts.data)?;
-                last_two_bytes[2] = self.nb.i2c.dr.read().dr().bits();
+                *next() = self.nb.i2c.dr.read().dr().bits();
 
-                busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+                if stop {
+                    busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+                }
                 self.nb.i2c.cr1.modify(|_, w| w.ack().set_bit());
             }
         }
 
         Ok(())
     }
+
+    /// Waits for the START condition of an operation. Only the START of a transaction is requested
+    /// here, a repeated START is requested at the end of the previous operation.
+    fn start_operation_and_wait(&mut self, repeated: bool) -> NbResult<(), Error> {
+        if repeated {
+            busy_wait_cycles!(self.wait_after_sent_start(), self.timeouts.start)
+        } else {
+            self.send_start_and_wait()
+        }
+    }
+
+    /// Runs `operations` on the device at `address` in a single transaction
+    ///
+    /// Adjacent operations of the same kind are merged, a repeated START and the address are sent
+    /// between operations of a different kind. The last byte of a read is only not acknowledged
+    /// if it ends the transaction or is followed by a write. Empty reads are skipped.
+    ///
+    /// This is also the way to talk to devices with a 10-bit address, the embedded-hal traits are
+    /// only implemented for 7-bit addresses.
+    pub fn transaction(
+        &mut self,
+        address: Address,
+        operations: &mut [Operation],
+    ) -> Result<(), NbError<Error>> {
+        let mut index = 0;
+        let mut started = false;
+        while index < operations.len() {
+            let read = matches!(operations[index], Operation::Read(_));
+            let end = operations[index..]
+                .iter()
+                .position(|operation| matches!(operation, Operation::Read(_)) != read)
+                .map_or(operations.len(), |len| index + len);
+            // Only skipped empty reads may follow the last operation
+            let last = operations[end..]
+                .iter()
+                .all(|operation| matches!(operation, Operation::Read(buffer) if buffer.is_empty()));
+            let run = &mut operations[index..end];
+            index = end;
+
+            if read {
+                let len = run
+                    .iter()
+                    .map(|operation| match operation {
+                        Operation::Read(buffer) => buffer.len(),
+                        Operation::Write(_) => 0,
+                    })
+                    .sum();
+                if len == 0 {
+                    continue;
+                }
+                let buffer = run.iter_mut().flat_map(|operation| match operation {
+                    Operation::Read(buffer) => buffer.iter_mut(),
+                    Operation::Write(_) => [].iter_mut(),
+                });
+
+                self.start_operation_and_wait(started)?;
+                self.send_address_and_wait(address, true)?;
+                // The STOP or repeated START is requested together with the last byte
+                self.read_bytes_and_wait(len, buffer, last)?;
+            } else {
+                let bytes = run.iter().flat_map(|operation| match operation {
+                    Operation::Write(bytes) => bytes.iter().copied(),
+                    Operation::Read(_) => [].iter().copied(),
+                });
+
+                self.start_operation_and_wait(started)?;
+                self.send_address_and_wait(address, false)?;
+                let ret = self.write_bytes_and_wait(bytes);
+                if ret == Err(Other(Error::Acknowledge)) {
+                    self.nb.send_stop();
+                }
+                ret?;
+
+                self.send_stop_or_start(last);
+                if last {
+                    busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+                }
+            }
+            started = true;
+        }
+
+        Ok(())
+    }
+}
+
+impl<I2C, PINS> BlockingI2c<I2C, PINS>
+where
+    I2C: Instance,
+    PINS: RecoverPins,
+{
+    /// Frees the bus when a slave holds SDA low, see [`I2c::recover_bus`]
+    ///
+    /// Useful after a transfer has failed with a timeout or `Error::Bus`.
+    pub fn recover_bus(&mut self, cr: &mut PINS::Cr) -> Result<(), Error> {
+        self.nb.recover_bus(cr)
+    }
+}
+
+impl<I2C, PINS> Write for BlockingI2c<I2C, PINS>
+where
+    I2C: Instance,
+{
+    type Error = NbError<Error>;
+
+    fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.transaction(Address::SevenBit(addr), &mut [Operation::Write(bytes)])
+    }
+}
+
+impl<I2C, PINS> Read for BlockingI2c<I2C, PINS>
+where
+    I2C: Instance,
+{
+    type Error = NbError<Error>;
+
+    fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.transaction(Address::SevenBit(addr), &mut [Operation::Read(buffer)])
+    }
 }
 
 impl<I2C, PINS> WriteRead for BlockingI2c<I2C, PINS>
@@ -357,17 +508,25 @@ where
     type Error = NbError<Error>;
 
     fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
-        if !bytes.is_empty() {
-            self.write_without_stop(addr, bytes)?;
+        let address = Address::SevenBit(addr);
+        match (bytes.is_empty(), buffer.is_empty()) {
+            (true, true) => Ok(()),
+            (true, false) => self.transaction(address, &mut [Operation::Read(buffer)]),
+            _ => self.transaction(
+                address,
+                &mut [Operation::Write(bytes), Operation::Read(buffer)],
+            ),
         }
+    }
+}
 
-        if !buffer.is_empty() {
-            self.read(addr, buffer)?;
-        } else if !bytes.is_empty() {
-            self.nb.send_stop();
-            busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
-        }
+impl<I2C, PINS> Transactional for BlockingI2c<I2C, PINS>
+where
+    I2C: Instance,
+{
+    type Error = NbError<Error>;
 
-        Ok(())
+    fn exec(&mut self, addr: u8, operations: &mut [Operation]) -> Result<(), Self::Error> {
+        self.transaction(Address::SevenBit(addr), operations)
     }
 }
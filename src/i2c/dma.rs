@@ -0,0 +1,337 @@
+//! # I2C master DMA transfers
+//!
+//! The data bytes of a transfer are moved by DMA through the [`WriteDma`] and [`ReadDma`] traits.
+//! The start condition and the address are sent by the CPU beforehand with `address`, which hands
+//! back the DMA payload together with the error if the device does not acknowledge its address.
+//!
+//! For reads, the LAST bit makes the I2C not acknowledge the final byte, the stop condition is
+//! generated by [`Transfer::wait`] once the DMA has finished. A single byte is not acknowledged
+//! and followed by the stop condition as soon as the read is started. For
+//! writes, [`Transfer::wait`] waits until the last byte has been sent before generating the stop.
+//!
+//! **Note:** after a read, the master keeps clocking in bytes until the stop condition has been
+//! generated. Call [`Transfer::wait`] as soon as the transfer is done, e.g. from the DMA transfer
+//! complete interrupt, instead of leaving a finished transfer around.
+//!
+//! `write_then_address` on the receive DMA writes a few bytes, usually a register address,
+//! without DMA and then addresses the device for reading after a repeated start.
+//!
+//! | Peripheral | TX channel | RX channel |
+//! |:----------:|:----------:|:----------:|
+//! | I2C1       | DMA1 C6    | DMA1 C7    |
+//! | I2C2       | DMA1 C4    | DMA1 C5    |
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! let i2c_dma = i2c.with_rx_dma(dma1.7);
+//! let buffer = singleton!(: [u8; 64] = [0; 64]).unwrap();
+//!
+//! let i2c_dma = i2c_dma.write_then_address(0x50, &[0x00, 0x00]).ok().unwrap();
+//! let (buffer, i2c_dma) = i2c_dma.read(buffer).wait();
+//!  ```
+
+use core::sync::atomic::{self, Ordering};
+
+use embedded_dma::{StaticReadBuffer, StaticWriteBuffer};
+
+use crate::dma::{
+    dma1, ReadDma, Receive, RxDma, Transfer, TransferPayload, Transmit, TxDma, WriteDma, R, W,
+};
+use crate::pac::i2c1::sr1;
+
+use super::*;
+
+pub type I2cTxDma<I2C, PINS, CHANNEL> = TxDma<I2c<I2C, PINS>, CHANNEL>;
+pub type I2cRxDma<I2C, PINS, CHANNEL> = RxDma<I2c<I2C, PINS>, CHANNEL>;
+
+impl<I2C, PINS> I2c<I2C, PINS>
+where
+    I2C: Instance,
+{
+    /// Busy waits for a status flag, returns the error flags in the meantime
+    fn wait_for(&self, flag: impl Fn(&sr1::R) -> bool) -> Result<(), Error> {
+        loop {
+            let sr1 = self.i2c.sr1.read();
+            if sr1.berr().bit_is_set() {
+                self.i2c.sr1.write(|w| w.berr().clear_bit());
+                return Err(Error::Bus);
+            } else if sr1.arlo().bit_is_set() {
+                self.i2c.sr1.write(|w| w.arlo().clear_bit());
+                return Err(Error::Arbitration);
+            } else if sr1.af().bit_is_set() {
+                self.i2c.sr1.write(|w| w.af().clear_bit());
+                self.send_stop();
+                return Err(Error::Acknowledge);
+            } else if flag(&sr1) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Sends a (repeated) start condition and the address, and waits until it is acknowledged.
+    /// ADDR is cleared for writes, for reads it is left to the read which knows its length.
+    fn start_dma(&mut self, address: u8, read: bool) -> Result<(), Error> {
+        // A start must not be requested before the previous stop has been generated
+        while self.i2c.cr1.read().stop().bit_is_set() {}
+
+        self.send_start();
+        self.wait_for(|sr1| sr1.sb().bit_is_set())?;
+        self.send_addr(address, read);
+        self.wait_for(|sr1| sr1.addr().bit_is_set())?;
+        if !read {
+            self.i2c.sr2.read();
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the master has addressed a device in the given direction
+    fn is_addressed(&self, transmitting: bool) -> bool {
+        let sr2 = self.i2c.sr2.read();
+        sr2.msl().bit_is_set() && sr2.tra().bit() == transmitting
+    }
+
+    /// Generates the stop condition after a DMA transfer
+    fn stop_dma(&mut self, read: bool) {
+        self.i2c.cr2.modify(|_, w| w.last().clear_bit());
+        if self.i2c.sr2.read().msl().bit_is_clear() {
+            // The transfer has been aborted by an error, the bus is already released
+            return;
+        }
+        if !read {
+            // The last byte is still being sent
+            self.wait_for(|sr1| sr1.btf().bit_is_set()).ok();
+        }
+        self.send_stop();
+        while self.i2c.cr1.read().stop().bit_is_set() {}
+    }
+}
+
+macro_rules! i2c_dma {
+    ($I2Ci:ident, $TCi:ty, $RCi:ty, $txdma:ident, $rxdma:ident) => {
+        pub type $txdma<PINS> = I2cTxDma<$I2Ci, PINS, $TCi>;
+        pub type $rxdma<PINS> = I2cRxDma<$I2Ci, PINS, $RCi>;
+
+        impl<PINS> Transmit for I2cTxDma<$I2Ci, PINS, $TCi> {
+            type TxChannel = $TCi;
+            type ReceivedWord = u8;
+        }
+
+        impl<PINS> Receive for I2cRxDma<$I2Ci, PINS, $RCi> {
+            type RxChannel = $RCi;
+            type TransmittedWord = u8;
+        }
+
+        impl<PINS> I2c<$I2Ci, PINS> {
+            pub fn with_tx_dma(self, channel: $TCi) -> I2cTxDma<$I2Ci, PINS, $TCi> {
+                self.i2c.cr2.modify(|_, w| w.dmaen().set_bit());
+                I2cTxDma {
+                    payload: self,
+                    channel,
+                }
+            }
+            pub fn with_rx_dma(self, channel: $RCi) -> I2cRxDma<$I2Ci, PINS, $RCi> {
+                self.i2c.cr2.modify(|_, w| w.dmaen().set_bit());
+                I2cRxDma {
+                    payload: self,
+                    channel,
+                }
+            }
+        }
+
+        impl<PINS> TransferPayload for I2cTxDma<$I2Ci, PINS, $TCi> {
+            fn start(&mut self) {
+                self.channel.start();
+            }
+            fn stop(&mut self) {
+                self.channel.stop();
+                self.payload.stop_dma(false);
+            }
+        }
+
+        impl<PINS> TransferPayload for I2cRxDma<$I2Ci, PINS, $RCi> {
+            fn start(&mut self) {
+                self.channel.start();
+            }
+            fn stop(&mut self) {
+                self.channel.stop();
+                self.payload.stop_dma(true);
+            }
+        }
+
+        impl<PINS> I2cTxDma<$I2Ci, PINS, $TCi> {
+            /// Sends a start condition and the address of the device the next transfer writes to
+            pub fn address(mut self, address: u8) -> Result<Self, (Error, Self)> {
+                match self.payload.start_dma(address, false) {
+                    Ok(()) => Ok(self),
+                    Err(error) => Err((error, self)),
+                }
+            }
+
+            pub fn release(self) -> (I2c<$I2Ci, PINS>, $TCi) {
+                let I2cTxDma { payload, channel } = self;
+                payload.i2c.cr2.modify(|_, w| w.dmaen().clear_bit());
+                (payload, channel)
+            }
+        }
+
+        impl<B, PINS> WriteDma<B, u8> for I2cTxDma<$I2Ci, PINS, $TCi>
+        where
+            B: StaticReadBuffer<Word = u8>,
+        {
+            /// Writes `buffer` to the device addressed with `address`
+            fn write(mut self, buffer: B) -> Transfer<R, B, Self> {
+                assert!(
+                    self.payload.is_addressed(true),
+                    "no device has been addressed for writing"
+                );
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.static_read_buffer() };
+                assert!(len > 0);
+                self.channel.set_peripheral_address(
+                    unsafe { &(*$I2Ci::ptr()).dr as *const _ as u32 },
+                    false,
+                );
+                self.channel.set_memory_address(ptr as u32, true);
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.ch().cr.modify(|_, w| {
+                    w
+                        // memory to memory mode disabled
+                        .mem2mem()
+                        .clear_bit()
+                        // medium channel priority level
+                        .pl()
+                        .medium()
+                        // 8-bit memory size
+                        .msize()
+                        .bits8()
+                        // 8-bit peripheral size
+                        .psize()
+                        .bits8()
+                        // circular mode disabled
+                        .circ()
+                        .clear_bit()
+                        // read from memory
+                        .dir()
+                        .set_bit()
+                });
+                self.start();
+
+                Transfer::r(buffer, self)
+            }
+        }
+
+        impl<PINS> I2cRxDma<$I2Ci, PINS, $RCi> {
+            /// Sends a start condition and the address of the device the next transfer reads from
+            pub fn address(self, address: u8) -> Result<Self, (Error, Self)> {
+                self.write_then_address(address, &[])
+            }
+
+            /// Writes `bytes` to the device at `address` without DMA, then addresses it for
+            /// reading after a repeated start
+            pub fn write_then_address(
+                mut self,
+                address: u8,
+                bytes: &[u8],
+            ) -> Result<Self, (Error, Self)> {
+                let i2c = &mut self.payload;
+                let result = (|| {
+                    if !bytes.is_empty() {
+                        i2c.start_dma(address, false)?;
+                        for byte in bytes {
+                            i2c.wait_for(|sr1| sr1.tx_e().bit_is_set())?;
+                            i2c.i2c.dr.write(|w| w.dr().bits(*byte));
+                        }
+                        i2c.wait_for(|sr1| sr1.btf().bit_is_set())?;
+                    }
+                    // Acknowledge all bytes but the last one
+                    i2c.i2c
+                        .cr1
+                        .modify(|_, w| w.ack().set_bit().pos().clear_bit());
+                    i2c.i2c.cr2.modify(|_, w| w.last().set_bit());
+                    i2c.start_dma(address, true)
+                })();
+
+                match result {
+                    Ok(()) => Ok(self),
+                    Err(error) => {
+                        self.payload.i2c.cr2.modify(|_, w| w.last().clear_bit());
+                        Err((error, self))
+                    }
+                }
+            }
+
+            pub fn release(self) -> (I2c<$I2Ci, PINS>, $RCi) {
+                let I2cRxDma { payload, channel } = self;
+                payload.i2c.cr2.modify(|_, w| w.dmaen().clear_bit());
+                (payload, channel)
+            }
+        }
+
+        impl<B, PINS> ReadDma<B, u8> for I2cRxDma<$I2Ci, PINS, $RCi>
+        where
+            B: StaticWriteBuffer<Word = u8>,
+        {
+            /// Fills `buffer` with bytes read from the device addressed with `address`
+            fn read(mut self, mut buffer: B) -> Transfer<W, B, Self> {
+                assert!(
+                    self.payload.i2c.sr1.read().addr().bit_is_set(),
+                    "no device has been addressed for reading"
+                );
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.static_write_buffer() };
+                assert!(len > 0);
+                self.channel.set_peripheral_address(
+                    unsafe { &(*$I2Ci::ptr()).dr as *const _ as u32 },
+                    false,
+                );
+                self.channel.set_memory_address(ptr as u32, true);
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.ch().cr.modify(|_, w| {
+                    w
+                        // memory to memory mode disabled
+                        .mem2mem()
+                        .clear_bit()
+                        // medium channel priority level
+                        .pl()
+                        .medium()
+                        // 8-bit memory size
+                        .msize()
+                        .bits8()
+                        // 8-bit peripheral size
+                        .psize()
+                        .bits8()
+                        // circular mode disabled
+                        .circ()
+                        .clear_bit()
+                        // write to memory
+                        .dir()
+                        .clear_bit()
+                });
+                self.start();
+
+                let i2c = &self.payload.i2c;
+                if len == 1 {
+                    // The only byte is not acknowledged, which has to be set up before ADDR is
+                    // cleared. The stop condition follows right after it.
+                    i2c.cr1.modify(|_, w| w.ack().clear_bit());
+                    i2c.sr2.read();
+                    self.payload.send_stop();
+                } else {
+                    i2c.sr2.read();
+                }
+
+                Transfer::w(buffer, self)
+            }
+        }
+    };
+}
+
+i2c_dma!(I2C1, dma1::C6, dma1::C7, I2c1TxDma, I2c1RxDma);
+i2c_dma!(I2C2, dma1::C4, dma1::C5, I2c2TxDma, I2c2RxDma);
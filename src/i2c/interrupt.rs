@@ -0,0 +1,419 @@
+//! # Interrupt driven I2C master
+//!
+//! [`InterruptI2c`] runs queued [`Transaction`]s from the I2Cx_EV and I2Cx_ER interrupts, so the
+//! application never waits for the bus. [`InterruptI2c::on_event`] and [`InterruptI2c::on_error`]
+//! must be called from the respective interrupt handlers.
+//!
+//! A transaction writes some bytes, reads some bytes, or writes and then reads after a repeated
+//! start. Its buffers are `'static` as they are accessed from the interrupts. Finished
+//! transactions are handed back in submission order by [`InterruptI2c::poll`], together with their
+//! result.
+//!
+//! The transaction queue is provided by the application as an array of [`Slot`]s.
+//!
+//! A transaction can only be started once the stop condition of the previous one has been
+//! generated, which takes about one SCL period. The interrupt waits at most two SCL periods for
+//! it. If a slave stretches the clock for longer, the next transaction is started by the next call
+//! of [`InterruptI2c::poll`] or [`InterruptI2c::submit`] instead.
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! static mut QUEUE: [Slot; 4] = [Slot::EMPTY; 4];
+//! static mut REGISTER: [u8; 1] = [0x3b];
+//! static mut DATA: [u8; 14] = [0; 14];
+//!
+//! let mut i2c = I2c::i2c1(dp.I2C1, pins, &mut afio.mapr, 400.khz(), clocks)
+//!     .interrupt(unsafe { &mut QUEUE });
+//!
+//! let transaction = Transaction::write_read(0x68, unsafe { &REGISTER }, unsafe { &mut DATA });
+//! i2c.submit(transaction).ok();
+//!
+//! // ... in the I2C1_EV and I2C1_ER interrupts
+//! i2c.on_event();
+//! i2c.on_error();
+//!
+//! // ... in the control loop
+//! if let Some((transaction, result)) = i2c.poll() {
+//!     let (_, data) = transaction.release();
+//! }
+//!  ```
+
+use super::*;
+
+/// A transfer to or from one device
+pub struct Transaction {
+    address: u8,
+    write: &'static [u8],
+    read: &'static mut [u8],
+}
+
+impl Transaction {
+    /// Writes `bytes` to the device at `address`
+    pub fn write(address: u8, bytes: &'static [u8]) -> Self {
+        Transaction {
+            address,
+            write: bytes,
+            read: &mut [],
+        }
+    }
+
+    /// Fills `buffer` with bytes read from the device at `address`
+    pub fn read(address: u8, buffer: &'static mut [u8]) -> Self {
+        Transaction {
+            address,
+            write: &[],
+            read: buffer,
+        }
+    }
+
+    /// Writes `bytes` to the device at `address`, then reads into `buffer` after a repeated start
+    pub fn write_read(address: u8, bytes: &'static [u8], buffer: &'static mut [u8]) -> Self {
+        Transaction {
+            address,
+            write: bytes,
+            read: buffer,
+        }
+    }
+
+    /// Returns the device address
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Returns the written bytes and the read buffer
+    pub fn release(self) -> (&'static [u8], &'static mut [u8]) {
+        (self.write, self.read)
+    }
+}
+
+/// Storage for one queued transaction
+pub struct Slot(Option<(Transaction, Option<Result<(), Error>>)>);
+
+impl Slot {
+    pub const EMPTY: Slot = Slot(None);
+}
+
+/// Progress of the active transaction
+#[derive(Clone, Copy)]
+enum Phase {
+    Idle,
+    /// The next transaction waits for the stop condition of the previous one
+    Pending,
+    /// Number of bytes written
+    Write(usize),
+    /// The read waits for the start condition and the acknowledge of the address
+    ReadAddress,
+    /// Number of bytes read
+    Read(usize),
+}
+
+/// I2C master driven by the event and error interrupts
+pub struct InterruptI2c<I2C, PINS> {
+    nb: I2c<I2C, PINS>,
+    queue: &'static mut [Slot],
+    /// Index of the oldest transaction
+    head: usize,
+    /// Number of queued transactions
+    len: usize,
+    /// Number of finished transactions which have not been polled yet
+    finished: usize,
+    phase: Phase,
+}
+
+impl<I2C, PINS> I2c<I2C, PINS>
+where
+    I2C: Instance,
+{
+    /// Generates an interrupt driven I2C master which queues up to `queue.len()` transactions
+    pub fn interrupt(self, queue: &'static mut [Slot]) -> InterruptI2c<I2C, PINS> {
+        assert!(!queue.is_empty());
+        for slot in queue.iter_mut() {
+            slot.0 = None;
+        }
+        self.i2c
+            .cr2
+            .modify(|_, w| w.itevten().set_bit().iterren().set_bit());
+        InterruptI2c {
+            nb: self,
+            queue,
+            head: 0,
+            len: 0,
+            finished: 0,
+            phase: Phase::Idle,
+        }
+    }
+}
+
+impl<I2C, PINS> InterruptI2c<I2C, PINS>
+where
+    I2C: Instance,
+{
+    /// Queues `transaction`, or returns it if the queue is full
+    pub fn submit(&mut self, transaction: Transaction) -> Result<(), Transaction> {
+        if self.len == self.queue.len() {
+            return Err(transaction);
+        }
+        let index = (self.head + self.len) % self.queue.len();
+        self.queue[index].0 = Some((transaction, None));
+        self.len += 1;
+
+        if let Phase::Idle | Phase::Pending = self.phase {
+            self.start_next();
+        }
+        Ok(())
+    }
+
+    /// Returns the oldest finished transaction and its result
+    pub fn poll(&mut self) -> Option<(Transaction, Result<(), Error>)> {
+        if let Phase::Pending = self.phase {
+            self.start_next();
+        }
+        if self.finished == 0 {
+            return None;
+        }
+        let (transaction, result) = self.queue[self.head].0.take()?;
+        self.head = (self.head + 1) % self.queue.len();
+        self.len -= 1;
+        self.finished -= 1;
+        Some((transaction, result.unwrap_or(Err(Error::Bus))))
+    }
+
+    /// Returns `true` if no transaction is waiting or running
+    pub fn is_idle(&self) -> bool {
+        self.len == self.finished
+    }
+
+    /// Handles the event interrupt
+    pub fn on_event(&mut self) {
+        if let Some(result) = self.step() {
+            self.complete(result);
+        }
+    }
+
+    /// Handles the error interrupt
+    pub fn on_error(&mut self) {
+        let i2c = &self.nb.i2c;
+        let sr1 = i2c.sr1.read();
+
+        let error = if sr1.berr().bit_is_set() {
+            i2c.sr1.write(|w| w.berr().clear_bit());
+            Error::Bus
+        } else if sr1.arlo().bit_is_set() {
+            // The bus has already been released
+            i2c.sr1.write(|w| w.arlo().clear_bit());
+            Error::Arbitration
+        } else if sr1.af().bit_is_set() {
+            i2c.sr1.write(|w| w.af().clear_bit());
+            self.nb.send_stop();
+            Error::Acknowledge
+        } else if sr1.ovr().bit_is_set() {
+            i2c.sr1.write(|w| w.ovr().clear_bit());
+            Error::Overrun
+        } else {
+            return;
+        };
+
+        if let Phase::Idle | Phase::Pending = self.phase {
+            return;
+        }
+        self.complete(Err(error));
+    }
+
+    /// Stops listening to the interrupts and returns the I2C master and the queue
+    ///
+    /// Queued transactions are dropped, the running one is cut off.
+    pub fn release(self) -> (I2c<I2C, PINS>, &'static mut [Slot]) {
+        self.nb.i2c.cr2.modify(|_, w| {
+            w.itevten()
+                .clear_bit()
+                .itbufen()
+                .clear_bit()
+                .iterren()
+                .clear_bit()
+        });
+        (self.nb, self.queue)
+    }
+
+    /// Advances the active transaction, returns its result once it is finished
+    fn step(&mut self) -> Option<Result<(), Error>> {
+        let index = (self.head + self.finished) % self.queue.len();
+        let i2c = &self.nb.i2c;
+        let (transaction, _) = self.queue[index].0.as_mut()?;
+        let sr1 = i2c.sr1.read();
+
+        match self.phase {
+            Phase::Idle | Phase::Pending => None,
+            Phase::Write(written) => {
+                let bytes = transaction.write;
+                if sr1.sb().bit_is_set() {
+                    self.nb.send_addr(transaction.address, false);
+                    None
+                } else if sr1.addr().bit_is_set() {
+                    i2c.sr2.read();
+                    if bytes.is_empty() {
+                        self.end_write()
+                    } else {
+                        i2c.dr.write(|w| w.dr().bits(bytes[0]));
+                        self.phase = Phase::Write(1);
+                        if bytes.len() == 1 {
+                            // Wait for BTF once the last byte is in the data register
+                            i2c.cr2.modify(|_, w| w.itbufen().clear_bit());
+                        }
+                        None
+                    }
+                } else if written < bytes.len() && sr1.tx_e().bit_is_set() {
+                    i2c.dr.write(|w| w.dr().bits(bytes[written]));
+                    self.phase = Phase::Write(written + 1);
+                    if written + 1 == bytes.len() {
+                        i2c.cr2.modify(|_, w| w.itbufen().clear_bit());
+                    }
+                    None
+                } else if written == bytes.len() && sr1.btf().bit_is_set() {
+                    self.end_write()
+                } else {
+                    None
+                }
+            }
+            // BTF of the written bytes stays set until the repeated start has been generated, so
+            // the data flags are only looked at once the address has been acknowledged
+            Phase::ReadAddress => {
+                let len = transaction.read.len();
+                if sr1.sb().bit_is_set() {
+                    // POS makes ACK apply to the second byte, as needed for two byte reads
+                    i2c.cr1.modify(|_, w| w.ack().set_bit().pos().bit(len == 2));
+                    self.nb.send_addr(transaction.address, true);
+                    None
+                } else if sr1.addr().bit_is_set() {
+                    match len {
+                        1 => {
+                            // The only byte is not acknowledged, the stop follows right after it
+                            i2c.cr1.modify(|_, w| w.ack().clear_bit());
+                            i2c.sr2.read();
+                            self.nb.send_stop();
+                        }
+                        2 => {
+                            i2c.sr2.read();
+                            i2c.cr1.modify(|_, w| w.ack().clear_bit());
+                            // Both bytes are read once BTF is set
+                            i2c.cr2.modify(|_, w| w.itbufen().clear_bit());
+                        }
+                        3 => {
+                            i2c.sr2.read();
+                            i2c.cr2.modify(|_, w| w.itbufen().clear_bit());
+                        }
+                        _ => {
+                            i2c.sr2.read();
+                        }
+                    }
+                    self.phase = Phase::Read(0);
+                    None
+                } else {
+                    None
+                }
+            }
+            Phase::Read(read) => {
+                let buffer = &mut *transaction.read;
+                let len = buffer.len();
+                if len == 1 && sr1.rx_ne().bit_is_set() {
+                    buffer[0] = i2c.dr.read().dr().bits();
+                    Some(Ok(()))
+                } else if len == 2 && sr1.btf().bit_is_set() {
+                    self.nb.send_stop();
+                    buffer[0] = i2c.dr.read().dr().bits();
+                    buffer[1] = i2c.dr.read().dr().bits();
+                    Some(Ok(()))
+                } else if len > 2 && len - read > 3 && sr1.rx_ne().bit_is_set() {
+                    buffer[read] = i2c.dr.read().dr().bits();
+                    self.phase = Phase::Read(read + 1);
+                    if len - read - 1 == 3 {
+                        // The last three bytes are handled from BTF
+                        i2c.cr2.modify(|_, w| w.itbufen().clear_bit());
+                    }
+                    None
+                } else if len > 2 && len - read == 3 && sr1.btf().bit_is_set() {
+                    // Byte N-2 is in the data register and N-1 in the shift register
+                    i2c.cr1.modify(|_, w| w.ack().clear_bit());
+                    buffer[read] = i2c.dr.read().dr().bits();
+                    self.nb.send_stop();
+                    buffer[read + 1] = i2c.dr.read().dr().bits();
+                    self.phase = Phase::Read(read + 2);
+                    i2c.cr2.modify(|_, w| w.itbufen().set_bit());
+                    None
+                } else if len > 2 && len - read == 1 && sr1.rx_ne().bit_is_set() {
+                    buffer[read] = i2c.dr.read().dr().bits();
+                    Some(Ok(()))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Continues after the last written byte, with a repeated start or with a stop
+    fn end_write(&mut self) -> Option<Result<(), Error>> {
+        let index = (self.head + self.finished) % self.queue.len();
+        let (transaction, _) = self.queue[index].0.as_ref()?;
+        if transaction.read.is_empty() {
+            self.nb.send_stop();
+            Some(Ok(()))
+        } else {
+            self.phase = Phase::ReadAddress;
+            self.nb.i2c.cr2.modify(|_, w| w.itbufen().set_bit());
+            self.nb.send_start();
+            None
+        }
+    }
+
+    /// Stores the result of the active transaction and starts the next one
+    fn complete(&mut self, result: Result<(), Error>) {
+        let index = (self.head + self.finished) % self.queue.len();
+        if let Some((_, slot_result)) = self.queue[index].0.as_mut() {
+            *slot_result = Some(result);
+        }
+        self.finished += 1;
+        self.phase = Phase::Idle;
+
+        self.nb.i2c.cr2.modify(|_, w| w.itbufen().clear_bit());
+        self.nb
+            .i2c
+            .cr1
+            .modify(|_, w| w.pos().clear_bit().ack().set_bit());
+
+        self.start_next();
+    }
+
+    /// Starts the oldest transaction which has not been run yet
+    fn start_next(&mut self) {
+        if self.finished == self.len {
+            return;
+        }
+        let index = (self.head + self.finished) % self.queue.len();
+        let writes = match self.queue[index].0.as_ref() {
+            Some((transaction, _)) => !transaction.write.is_empty() || transaction.read.is_empty(),
+            None => return,
+        };
+
+        // A start must not be requested before the previous stop has been generated. There is no
+        // interrupt for that, if it takes too long the transaction is started by the next `poll`
+        // or `submit`.
+        if !self.wait_for_stop() {
+            self.phase = Phase::Pending;
+            return;
+        }
+
+        self.phase = if writes {
+            Phase::Write(0)
+        } else {
+            Phase::ReadAddress
+        };
+        self.nb.i2c.cr2.modify(|_, w| w.itbufen().set_bit());
+        self.nb.send_start();
+    }
+    /// Waits at most two SCL periods for the stop condition, returns whether it has been generated
+    fn wait_for_stop(&self) -> bool {
+        // Every read of the register takes at least one PCLK1 cycle
+        let cycles = 2 * self.nb.pclk1 / self.nb.mode.get_frequency().0;
+        (0..=cycles).any(|_| self.nb.i2c.cr1.read().stop().bit_is_clear())
+    }
+}
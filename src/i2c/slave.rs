@@ -0,0 +1,301 @@
+//! # I2C slave
+//!
+//! [`I2cSlave`] answers to one or two own addresses and optionally to the general call address.
+//! The bus traffic is reported as [`Event`]s by [`I2cSlave::poll`], which can either be called in
+//! a loop or from the I2Cx_EV and I2Cx_ER interrupt handlers after calling [`I2cSlave::listen`].
+//!
+//! With clock stretching enabled (the default) the slave holds the clock low until the
+//! application has handled the event, so it does not have to react within a bit time.
+//!
+//! With [`Config::smbus_device`] the slave runs as SMBus device and optionally answers to the
+//! SMBus device default address used by the address resolution protocol.
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! let config = Config::new(Address::SevenBit(0x42));
+//! let mut slave = I2cSlave::i2c1(dp.I2C1, (scl, sda), &mut afio.mapr, config, clocks);
+//! slave.listen();
+//!
+//! // ... in the I2C1_EV and I2C1_ER interrupts
+//! while let Ok(event) = slave.poll() {
+//!     match event {
+//!         Event::AddressMatched(_) => {}
+//!         Event::ByteReceived(byte) => registers[index] = byte,
+//!         Event::ByteRequested => slave.write(registers[index]),
+//!         Event::Stop => {}
+//!     }
+//! }
+//!  ```
+
+use super::*;
+
+/// I2C slave configuration
+pub struct Config {
+    pub own_address: Address,
+    /// Second 7-bit address
+    pub secondary_address: Option<u8>,
+    pub general_call: bool,
+    pub clock_stretching: bool,
+    /// Runs the peripheral as SMBus device, which reports a clock held low for more than 25 ms
+    /// as `Error::Timeout`
+    pub smbus_device: bool,
+    /// Answers to the SMBus device default address, in SMBus device mode only
+    pub arp: bool,
+}
+
+impl Config {
+    /// Creates a configuration which answers to `own_address` with clock stretching enabled
+    pub fn new(own_address: Address) -> Self {
+        Config {
+            own_address,
+            secondary_address: None,
+            general_call: false,
+            clock_stretching: true,
+            smbus_device: false,
+            arp: false,
+        }
+    }
+
+    pub fn secondary_address(mut self, address: u8) -> Self {
+        self.secondary_address = Some(address);
+        self
+    }
+
+    pub fn general_call(mut self, enabled: bool) -> Self {
+        self.general_call = enabled;
+        self
+    }
+
+    pub fn clock_stretching(mut self, enabled: bool) -> Self {
+        self.clock_stretching = enabled;
+        self
+    }
+
+    /// Switches to SMBus device mode, the packet error code can be computed with
+    /// [`smbus::Pec`](super::smbus::Pec)
+    pub fn smbus_device(mut self, arp: bool) -> Self {
+        self.smbus_device = true;
+        self.arp = arp;
+        self
+    }
+}
+
+/// Direction of a transfer, seen from the master
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// The master writes, the slave receives
+    Write,
+    /// The master reads, the slave transmits
+    Read,
+}
+
+/// Address the slave has been addressed with
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MatchedAddress {
+    Primary,
+    Secondary,
+    GeneralCall,
+    /// SMBus device default address, see [`Config::smbus_device`]
+    SmBusDefault,
+}
+
+/// Slave event
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// The slave has been addressed, the matched address is returned by
+    /// [`I2cSlave::matched_address`]
+    AddressMatched(Direction),
+    /// The master has written a byte
+    ByteReceived(u8),
+    /// The master reads a byte, which has to be passed to [`I2cSlave::write`] before polling again
+    ByteRequested,
+    /// The master has ended the transfer with a stop condition, or by not acknowledging the
+    /// last byte it has read
+    Stop,
+}
+
+/// I2C peripheral operating in slave mode
+pub struct I2cSlave<I2C, PINS> {
+    i2c: I2C,
+    pins: PINS,
+    matched_address: MatchedAddress,
+    transmitting: bool,
+    listening: bool,
+}
+
+impl<PINS> I2cSlave<I2C1, PINS> {
+    /// Creates an I2C1 slave on pins PB6 and PB7 or PB8 and PB9 (if remapped)
+    pub fn i2c1(i2c: I2C1, pins: PINS, mapr: &mut MAPR, config: Config, clocks: Clocks) -> Self
+    where
+        PINS: Pins<I2C1>,
+    {
+        mapr.modify_mapr(|_, w| w.i2c1_remap().bit(PINS::REMAP));
+        I2cSlave::<I2C1, _>::new(i2c, pins, config, clocks)
+    }
+}
+
+impl<PINS> I2cSlave<I2C2, PINS> {
+    /// Creates an I2C2 slave on pins PB10 and PB11
+    pub fn i2c2(i2c: I2C2, pins: PINS, config: Config, clocks: Clocks) -> Self
+    where
+        PINS: Pins<I2C2>,
+    {
+        I2cSlave::<I2C2, _>::new(i2c, pins, config, clocks)
+    }
+}
+
+impl<I2C, PINS> I2cSlave<I2C, PINS>
+where
+    I2C: Instance,
+{
+    fn new(i2c: I2C, pins: PINS, config: Config, clocks: Clocks) -> Self {
+        let rcc = unsafe { &(*RCC::ptr()) };
+        I2C::enable(rcc);
+        I2C::reset(rcc);
+
+        // The peripheral clock frequency is also needed for the slave timings
+        let pclk1_mhz = I2C::get_frequency(&clocks).0 / 1_000_000;
+        i2c.cr2.write(|w| unsafe { w.freq().bits(pclk1_mhz as u8) });
+
+        // Bit 14 of OAR1 must be kept at 1
+        i2c.oar1.write(|w| {
+            let w = unsafe { w.bits(1 << 14) };
+            match config.own_address {
+                Address::SevenBit(address) => {
+                    w.addmode().add7().add().bits(u16::from(address) << 1)
+                }
+                Address::TenBit(address) => w.addmode().add10().add().bits(address),
+            }
+        });
+        i2c.oar2.write(|w| match config.secondary_address {
+            Some(address) => w.add2().bits(address).endual().dual(),
+            None => w.endual().single(),
+        });
+
+        i2c.cr1.write(|w| {
+            w.engc()
+                .bit(config.general_call)
+                .nostretch()
+                .bit(!config.clock_stretching)
+                .smbus()
+                .bit(config.smbus_device)
+                .smbtype()
+                .device()
+                .enarp()
+                .bit(config.arp)
+                .pe()
+                .set_bit()
+        });
+        // ACK is cleared while the peripheral is disabled
+        i2c.cr1.modify(|_, w| w.ack().set_bit());
+
+        I2cSlave {
+            i2c,
+            pins,
+            matched_address: MatchedAddress::Primary,
+            transmitting: false,
+            listening: false,
+        }
+    }
+
+    /// Starts listening to the event, buffer and error interrupts
+    pub fn listen(&mut self) {
+        self.listening = true;
+        self.i2c.cr2.modify(|_, w| {
+            w.itevten()
+                .set_bit()
+                .itbufen()
+                .set_bit()
+                .iterren()
+                .set_bit()
+        });
+    }
+
+    /// Stops listening to the interrupts
+    pub fn unlisten(&mut self) {
+        self.listening = false;
+        self.i2c.cr2.modify(|_, w| {
+            w.itevten()
+                .clear_bit()
+                .itbufen()
+                .clear_bit()
+                .iterren()
+                .clear_bit()
+        });
+    }
+
+    /// Returns the next event, or `WouldBlock` if nothing has happened
+    pub fn poll(&mut self) -> NbResult<Event, Error> {
+        let sr1 = self.i2c.sr1.read();
+
+        if sr1.berr().bit_is_set() {
+            self.i2c.sr1.write(|w| w.berr().clear_bit());
+            Err(Other(Error::Bus))
+        } else if sr1.ovr().bit_is_set() {
+            self.i2c.sr1.write(|w| w.ovr().clear_bit());
+            Err(Other(Error::Overrun))
+        } else if sr1.timeout().bit_is_set() {
+            self.i2c.sr1.write(|w| w.timeout().clear_bit());
+            self.transmitting = false;
+            Err(Other(Error::Timeout))
+        } else if sr1.addr().bit_is_set() {
+            // Reading sr2 after sr1 clears ADDR
+            let sr2 = self.i2c.sr2.read();
+            self.matched_address = if sr2.gencall().bit_is_set() {
+                MatchedAddress::GeneralCall
+            } else if sr2.smbdefault().bit_is_set() {
+                MatchedAddress::SmBusDefault
+            } else if sr2.dualf().bit_is_set() {
+                MatchedAddress::Secondary
+            } else {
+                MatchedAddress::Primary
+            };
+            self.transmitting = sr2.tra().bit_is_set();
+            if self.listening {
+                self.i2c.cr2.modify(|_, w| w.itbufen().set_bit());
+            }
+            Ok(Event::AddressMatched(if self.transmitting {
+                Direction::Read
+            } else {
+                Direction::Write
+            }))
+        } else if sr1.rx_ne().bit_is_set() {
+            Ok(Event::ByteReceived(self.i2c.dr.read().dr().bits()))
+        } else if sr1.af().bit_is_set() {
+            // The master does not acknowledge the last byte it reads. This has to be checked
+            // before TXE, otherwise a stale byte would be sent in the next transfer.
+            self.i2c.sr1.write(|w| w.af().clear_bit());
+            self.transmitting = false;
+            // TXE stays set until the next transfer, which would keep triggering the interrupt
+            self.i2c.cr2.modify(|_, w| w.itbufen().clear_bit());
+            Ok(Event::Stop)
+        } else if sr1.stopf().bit_is_set() {
+            // Writing cr1 after reading sr1 clears STOPF
+            self.i2c.cr1.modify(|_, w| w);
+            self.transmitting = false;
+            Ok(Event::Stop)
+        } else if sr1.tx_e().bit_is_set() && self.transmitting {
+            Ok(Event::ByteRequested)
+        } else {
+            Err(WouldBlock)
+        }
+    }
+
+    /// Sends `byte` in answer to [`Event::ByteRequested`]
+    pub fn write(&mut self, byte: u8) {
+        self.i2c.dr.write(|w| w.dr().bits(byte));
+    }
+
+    /// Returns the address of the last [`Event::AddressMatched`]
+    pub fn matched_address(&self) -> MatchedAddress {
+        self.matched_address
+    }
+
+    /// Disables the peripheral and returns it together with the pins
+    pub fn release(self) -> (I2C, PINS) {
+        self.i2c.cr2.reset();
+        self.i2c.cr1.reset();
+        (self.i2c, self.pins)
+    }
+}
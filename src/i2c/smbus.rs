@@ -0,0 +1,561 @@
+//! # SMBus host
+//!
+//! [`SmBus`] runs the I2C peripheral in SMBus host mode and provides the SMBus protocols as typed
+//! commands. Word data is sent least significant byte first.
+//!
+//! The packet error code is computed in software by [`Pec`] by default. With
+//! [`Config::hardware_pec`] the peripheral computes, sends and checks it instead. Either way a wrong
+//! code is reported as `Error::Pec`. In SMBus mode the peripheral flags a clock held low for more than 25 ms, which is
+//! reported as `Error::Timeout`.
+//!
+//! Devices signal an alert by pulling SMBA low (PB5 for I2C1, PB12 for I2C2). The alert is
+//! reported as `Error::Alert` by [`SmBus::check_alert`], the address of the alerting device is
+//! then read from the alert response address with [`SmBus::alert_response`].
+//!
+//! The SMBus device mode is provided by the I2C slave, see [`slave::Config::smbus_device`].
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! let mut smbus = i2c.smbus_with_alert(gpiob.pb5, Config::new().pec(true));
+//!
+//! // Voltage of a smart battery
+//! let millivolts = smbus.read_word(0x0b, 0x09)?;
+//! // Manufacturer name
+//! let mut name = [0; 32];
+//! let len = smbus.block_read(0x0b, 0x20, &mut name)?;
+//!
+//! if smbus.check_alert() == Err(Error::Alert) {
+//!     let device = smbus.alert_response()?;
+//! }
+//!  ```
+
+use cortex_m::interrupt;
+
+use crate::gpio::gpiob::{PB12, PB5};
+use crate::gpio::Input;
+use crate::pac::i2c1::sr1;
+
+use super::*;
+
+/// Alert response address, answered by the devices which pull SMBA low
+pub const ALERT_RESPONSE_ADDRESS: u8 = 0x0c;
+
+/// Largest amount of data in a block transfer
+pub const BLOCK_MAX: usize = 32;
+
+/// SMBus packet error code, a CRC-8 with the polynomial x^8 + x^2 + x + 1
+///
+/// The code covers every byte of a message, including the address bytes with their read/write bit.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Pec(u8);
+
+impl Pec {
+    pub const fn new() -> Self {
+        Pec(0)
+    }
+
+    /// Adds `bytes` to the code
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            let mut crc = self.0 ^ byte;
+            for _ in 0..8 {
+                crc = if crc & 0x80 != 0 {
+                    (crc << 1) ^ 0x07
+                } else {
+                    crc << 1
+                };
+            }
+            self.0 = crc;
+        }
+    }
+
+    /// Returns the code of the bytes added so far
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+}
+
+/// Returns the packet error code of `bytes`
+pub fn pec(bytes: &[u8]) -> u8 {
+    let mut pec = Pec::new();
+    pec.update(bytes);
+    pec.value()
+}
+
+/// SMBus configuration
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Config {
+    /// Appends a packet error code to every message and checks the received ones
+    pub pec: bool,
+    /// Lets the peripheral compute and check the packet error code instead of [`Pec`]
+    pub hardware_pec: bool,
+    /// Answers to the SMBus host address
+    pub arp: bool,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pec(mut self, enabled: bool) -> Self {
+        self.pec = enabled;
+        self
+    }
+
+    /// Enables the packet error code and computes it in the peripheral
+    pub fn hardware_pec(mut self, enabled: bool) -> Self {
+        self.pec |= enabled;
+        self.hardware_pec = enabled;
+        self
+    }
+
+    pub fn arp(mut self, enabled: bool) -> Self {
+        self.arp = enabled;
+        self
+    }
+}
+
+/// SMBA pin
+pub trait AlertPin<I2C> {}
+
+impl<MODE> AlertPin<I2C1> for PB5<Input<MODE>> {}
+impl<MODE> AlertPin<I2C2> for PB12<Input<MODE>> {}
+
+/// A filler type for when the SMBA pin is not used
+pub struct NoAlert;
+
+/// I2C peripheral operating as SMBus host
+pub struct SmBus<I2C, PINS, ALERT> {
+    nb: I2c<I2C, PINS>,
+    alert: ALERT,
+    pec: bool,
+    hardware_pec: bool,
+}
+
+impl<I2C, PINS> I2c<I2C, PINS>
+where
+    I2C: Instance,
+{
+    /// Switches the peripheral to SMBus host mode
+    pub fn smbus(self, config: Config) -> SmBus<I2C, PINS, NoAlert> {
+        SmBus::new(self, NoAlert, config)
+    }
+
+    /// Switches the peripheral to SMBus host mode, alerts are detected on the SMBA pin
+    pub fn smbus_with_alert<ALERT>(self, alert: ALERT, config: Config) -> SmBus<I2C, PINS, ALERT>
+    where
+        ALERT: AlertPin<I2C>,
+    {
+        SmBus::new(self, alert, config)
+    }
+}
+
+impl<I2C, PINS, ALERT> SmBus<I2C, PINS, ALERT>
+where
+    I2C: Instance,
+{
+    fn new(nb: I2c<I2C, PINS>, alert: ALERT, config: Config) -> Self {
+        nb.i2c.cr1.modify(|_, w| w.pe().clear_bit());
+        nb.i2c.cr1.modify(|_, w| {
+            w.smbus()
+                .smbus()
+                .smbtype()
+                .host()
+                .enarp()
+                .bit(config.arp)
+                .enpec()
+                .bit(config.hardware_pec)
+        });
+        nb.i2c.cr1.modify(|_, w| w.pe().set_bit());
+
+        SmBus {
+            nb,
+            alert,
+            pec: config.pec,
+            hardware_pec: config.hardware_pec,
+        }
+    }
+
+    /// Busy waits for a status flag, returns the error flags in the meantime
+    fn wait_for(&self, flag: impl Fn(&sr1::R) -> bool) -> Result<(), Error> {
+        let i2c = &self.nb.i2c;
+        loop {
+            let sr1 = i2c.sr1.read();
+            if sr1.berr().bit_is_set() {
+                i2c.sr1.write(|w| w.berr().clear_bit());
+                return Err(Error::Bus);
+            } else if sr1.arlo().bit_is_set() {
+                i2c.sr1.write(|w| w.arlo().clear_bit());
+                return Err(Error::Arbitration);
+            } else if sr1.af().bit_is_set() {
+                i2c.sr1.write(|w| w.af().clear_bit());
+                self.nb.send_stop();
+                return Err(Error::Acknowledge);
+            } else if sr1.timeout().bit_is_set() {
+                i2c.sr1.write(|w| w.timeout().clear_bit());
+                self.nb.send_stop();
+                return Err(Error::Timeout);
+            } else if flag(&sr1) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Sends a (repeated) start condition and the address, ADDR is left set
+    fn start(&mut self, address: u8, read: bool) -> Result<(), Error> {
+        // A start must not be requested before the previous stop has been generated
+        while self.nb.i2c.cr1.read().stop().bit_is_set() {}
+
+        self.nb.send_start();
+        self.wait_for(|sr1| sr1.sb().bit_is_set())?;
+        self.nb.send_addr(address, read);
+        self.wait_for(|sr1| sr1.addr().bit_is_set())
+    }
+
+    /// Sends `bytes` after ADDR has been cleared and waits until the last one is on the bus
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        for byte in bytes {
+            self.wait_for(|sr1| sr1.tx_e().bit_is_set())?;
+            self.nb.i2c.dr.write(|w| w.dr().bits(*byte));
+        }
+        self.wait_for(|sr1| sr1.btf().bit_is_set())
+    }
+
+    /// Sends the packet error code after the last byte, if enabled
+    fn write_pec(&mut self, pec: &Pec) -> Result<(), Error> {
+        if self.hardware_pec {
+            // The peripheral appends its code once the last byte has left the data register
+            self.nb.i2c.cr1.modify(|_, w| w.pec().set_bit());
+            while self.nb.i2c.cr1.read().pec().bit_is_set() {}
+            self.wait_for(|sr1| sr1.btf().bit_is_set())
+        } else if self.pec {
+            self.write_bytes(&[pec.value()])
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks the received packet error code, `code` is empty if it is disabled
+    fn check_pec(&self, pec: &Pec, code: &[u8]) -> Result<(), Error> {
+        let i2c = &self.nb.i2c;
+        if self.hardware_pec {
+            if i2c.sr1.read().pecerr().bit_is_set() {
+                i2c.sr1.write(|w| w.pecerr().clear_bit());
+                Err(Error::Pec)
+            } else {
+                Ok(())
+            }
+        } else if self.pec && code[0] != pec.value() {
+            Err(Error::Pec)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sends the address for writing, the command and `bytes`, without stop condition
+    fn write_command(&mut self, address: u8, bytes: &[u8], pec: &mut Pec) -> Result<(), Error> {
+        self.start(address, false)?;
+        self.nb.i2c.sr2.read();
+        pec.update(&[address << 1]);
+        pec.update(bytes);
+        self.write_bytes(bytes)
+    }
+
+    /// Generates the stop condition and waits until it has been sent
+    fn stop(&mut self) {
+        self.nb.send_stop();
+        while self.nb.i2c.cr1.read().stop().bit_is_set() {}
+    }
+
+    /// Receives the rest of a read, the first byte of `buffer` is being received with ACK set.
+    /// With the hardware packet error code, the last byte is checked by the peripheral.
+    fn receive(&self, buffer: &mut [u8]) -> Result<(), Error> {
+        let i2c = &self.nb.i2c;
+        let len = buffer.len();
+        if len == 2 {
+            self.wait_for(|sr1| sr1.rx_ne().bit_is_set())?;
+            // The last byte is already being received, it is not acknowledged as long as ACK is
+            // cleared before it is complete
+            interrupt::free(|_| {
+                i2c.cr1.modify(|_, w| {
+                    w.ack()
+                        .clear_bit()
+                        .stop()
+                        .set_bit()
+                        .pec()
+                        .bit(self.hardware_pec)
+                });
+                buffer[0] = i2c.dr.read().dr().bits();
+            });
+        } else {
+            for byte in &mut buffer[..len - 3] {
+                self.wait_for(|sr1| sr1.rx_ne().bit_is_set())?;
+                *byte = i2c.dr.read().dr().bits();
+            }
+            // Byte N-2 in DR, byte N-1 in the shift register, the last one is not received yet
+            self.wait_for(|sr1| sr1.btf().bit_is_set())?;
+            i2c.cr1
+                .modify(|_, w| w.ack().clear_bit().pec().bit(self.hardware_pec));
+            buffer[len - 3] = i2c.dr.read().dr().bits();
+            self.nb.send_stop();
+            buffer[len - 2] = i2c.dr.read().dr().bits();
+        }
+        self.wait_for(|sr1| sr1.rx_ne().bit_is_set())?;
+        buffer[len - 1] = i2c.dr.read().dr().bits();
+        Ok(())
+    }
+
+    /// Receives `buffer` after the address has been acknowledged, ADDR is still set
+    fn read_bytes(&self, buffer: &mut [u8]) -> Result<(), Error> {
+        let i2c = &self.nb.i2c;
+        if buffer.len() == 1 {
+            i2c.cr1.modify(|_, w| w.ack().clear_bit());
+            i2c.sr2.read();
+            self.nb.send_stop();
+            self.wait_for(|sr1| sr1.rx_ne().bit_is_set())?;
+            buffer[0] = i2c.dr.read().dr().bits();
+        } else {
+            i2c.cr1.modify(|_, w| w.ack().set_bit());
+            i2c.sr2.read();
+            self.receive(buffer)?;
+        }
+        while i2c.cr1.read().stop().bit_is_set() {}
+        Ok(())
+    }
+
+    /// Writes `bytes`, starting with the command, then reads `buffer` after a repeated start.
+    /// Without `bytes`, only the read is done.
+    fn transfer(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        let mut pec = Pec::new();
+        if !bytes.is_empty() {
+            self.write_command(address, bytes, &mut pec)?;
+        }
+        self.start(address, true)?;
+        pec.update(&[address << 1 | 1]);
+
+        // Data and the packet error code
+        let mut received = [0; 3];
+        let len = buffer.len() + self.pec as usize;
+        let received = &mut received[..len];
+        self.read_bytes(received)?;
+
+        let (data, code) = received.split_at(buffer.len());
+        buffer.copy_from_slice(data);
+        pec.update(data);
+        self.check_pec(&pec, code)
+    }
+
+    /// Writes `bytes`, starting with the command, followed by the packet error code if enabled
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        let mut pec = Pec::new();
+        self.write_command(address, bytes, &mut pec)?;
+        self.write_pec(&pec)?;
+        self.stop();
+        Ok(())
+    }
+
+    /// Quick command, the read/write bit is the only data
+    pub fn quick_command(&mut self, address: u8, read: bool) -> Result<(), Error> {
+        self.start(address, read)?;
+        if read {
+            // The slave sends a byte anyway, which must not be acknowledged and is discarded
+            let result = self.read_bytes(&mut [0]);
+            self.nb.i2c.cr1.modify(|_, w| w.ack().set_bit());
+            result
+        } else {
+            self.nb.i2c.sr2.read();
+            self.stop();
+            Ok(())
+        }
+    }
+
+    /// Send byte, a single byte without command
+    pub fn send_byte(&mut self, address: u8, byte: u8) -> Result<(), Error> {
+        self.write(address, &[byte])
+    }
+
+    /// Receive byte, a single byte without command
+    pub fn receive_byte(&mut self, address: u8) -> Result<u8, Error> {
+        let mut buffer = [0];
+        self.transfer(address, &[], &mut buffer)?;
+        Ok(buffer[0])
+    }
+
+    pub fn write_byte(&mut self, address: u8, command: u8, byte: u8) -> Result<(), Error> {
+        self.write(address, &[command, byte])
+    }
+
+    pub fn write_word(&mut self, address: u8, command: u8, word: u16) -> Result<(), Error> {
+        let [low, high] = word.to_le_bytes();
+        self.write(address, &[command, low, high])
+    }
+
+    pub fn read_byte(&mut self, address: u8, command: u8) -> Result<u8, Error> {
+        let mut buffer = [0];
+        self.transfer(address, &[command], &mut buffer)?;
+        Ok(buffer[0])
+    }
+
+    pub fn read_word(&mut self, address: u8, command: u8) -> Result<u16, Error> {
+        let mut buffer = [0; 2];
+        self.transfer(address, &[command], &mut buffer)?;
+        Ok(u16::from_le_bytes(buffer))
+    }
+
+    /// Process call, writes a word and reads a word in one message
+    pub fn process_call(&mut self, address: u8, command: u8, word: u16) -> Result<u16, Error> {
+        let [low, high] = word.to_le_bytes();
+        let mut buffer = [0; 2];
+        self.transfer(address, &[command, low, high], &mut buffer)?;
+        Ok(u16::from_le_bytes(buffer))
+    }
+
+    /// Block write, sends the length of `bytes` followed by the bytes
+    pub fn block_write(&mut self, address: u8, command: u8, bytes: &[u8]) -> Result<(), Error> {
+        assert!(bytes.len() <= BLOCK_MAX);
+
+        let mut pec = Pec::new();
+        self.write_command(address, &[command, bytes.len() as u8], &mut pec)?;
+        pec.update(bytes);
+        self.write_bytes(bytes)?;
+        self.write_pec(&pec)?;
+        self.stop();
+        Ok(())
+    }
+
+    /// Block read, fills the start of `buffer` with the bytes sent by the device and returns
+    /// their number
+    ///
+    /// `Error::Overrun` is returned if the device sends more bytes than fit into `buffer` or more
+    /// than [`BLOCK_MAX`].
+    pub fn block_read(
+        &mut self,
+        address: u8,
+        command: u8,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut pec = Pec::new();
+        self.write_command(address, &[command], &mut pec)?;
+        self.start(address, true)?;
+        pec.update(&[address << 1 | 1]);
+
+        let i2c = &self.nb.i2c;
+        i2c.cr1.modify(|_, w| w.ack().set_bit());
+        i2c.sr2.read();
+        self.wait_for(|sr1| sr1.rx_ne().bit_is_set())?;
+
+        // The byte after the count is already being received, if it is the last one it must not
+        // be acknowledged
+        let max = buffer.len().min(BLOCK_MAX);
+        let count = interrupt::free(|_| {
+            let count = i2c.dr.read().dr().bits() as usize;
+            if count > max || count + self.pec as usize <= 1 {
+                i2c.cr1.modify(|_, w| {
+                    w.ack()
+                        .clear_bit()
+                        .stop()
+                        .set_bit()
+                        .pec()
+                        .bit(self.hardware_pec && count <= max)
+                });
+            }
+            count
+        });
+        pec.update(&[count as u8]);
+
+        let mut received = [0; BLOCK_MAX + 1];
+        let len = count + self.pec as usize;
+        if count > max || len <= 1 {
+            self.wait_for(|sr1| sr1.rx_ne().bit_is_set())?;
+            received[0] = i2c.dr.read().dr().bits();
+            while i2c.cr1.read().stop().bit_is_set() {}
+            if count > max {
+                return Err(Error::Overrun);
+            }
+        } else {
+            self.receive(&mut received[..len])?;
+            while i2c.cr1.read().stop().bit_is_set() {}
+        }
+
+        let (data, code) = received[..len].split_at(count);
+        buffer[..count].copy_from_slice(data);
+        pec.update(data);
+        self.check_pec(&pec, code)?;
+        Ok(count)
+    }
+
+    /// Switches the peripheral back to I2C mode and releases it together with the SMBA pin
+    pub fn release(self) -> (I2c<I2C, PINS>, ALERT) {
+        let i2c = &self.nb.i2c;
+        i2c.cr1.modify(|_, w| w.pe().clear_bit());
+        i2c.cr1.modify(|_, w| {
+            w.smbus()
+                .i2c()
+                .smbtype()
+                .device()
+                .enarp()
+                .disabled()
+                .enpec()
+                .disabled()
+        });
+        i2c.cr1.modify(|_, w| w.pe().set_bit());
+        (self.nb, self.alert)
+    }
+}
+
+impl<I2C, PINS, ALERT> SmBus<I2C, PINS, ALERT>
+where
+    I2C: Instance,
+    ALERT: AlertPin<I2C>,
+{
+    /// Returns `Error::Alert` if a device has pulled SMBA low since the last call
+    pub fn check_alert(&mut self) -> Result<(), Error> {
+        if self.nb.i2c.sr1.read().smbalert().bit_is_set() {
+            self.nb.i2c.sr1.write(|w| w.smbalert().clear_bit());
+            Err(Error::Alert)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Starts listening to the SMBA pin in the I2Cx_ER interrupt
+    pub fn listen_alert(&mut self) {
+        self.nb.i2c.cr2.modify(|_, w| w.iterren().set_bit());
+    }
+
+    /// Stops listening to the SMBA pin
+    pub fn unlisten_alert(&mut self) {
+        self.nb.i2c.cr2.modify(|_, w| w.iterren().clear_bit());
+    }
+
+    /// Reads the alert response address and returns the address of the device which has
+    /// pulled SMBA low
+    pub fn alert_response(&mut self) -> Result<u8, Error> {
+        Ok(self.receive_byte(ALERT_RESPONSE_ADDRESS)? >> 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pec_check_value() {
+        assert_eq!(pec(b"123456789"), 0xf4);
+        assert_eq!(pec(&[]), 0);
+    }
+
+    #[test]
+    fn pec_read_word() {
+        // Read word from address 0x5a, command 0x07, the device returns 0x3ad2
+        assert_eq!(pec(&[0xb4, 0x07, 0xb5, 0xd2, 0x3a]), 0x30);
+
+        let mut pec = Pec::new();
+        pec.update(&[0xb4, 0x07]);
+        pec.update(&[0xb5]);
+        pec.update(&[0xd2, 0x3a]);
+        assert_eq!(pec.value(), 0x30);
+    }
+}
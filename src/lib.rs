@@ -149,6 +149,10 @@ pub mod bb;
 #[cfg(all(feature = "device-selected", feature = "has-can"))]
 pub mod can;
 #[cfg(feature = "device-selected")]
+pub mod capture;
+#[cfg(feature = "device-selected")]
+pub mod compare;
+#[cfg(feature = "device-selected")]
 pub mod crc;
 #[cfg(feature = "device-selected")]
 pub mod delay;
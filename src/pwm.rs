@@ -5,6 +5,13 @@
   pulse width modulated signals on some pins. The timers support up to 4
   simultaneous pwm outputs in separate `Channels`
 
+  The advanced control timers `TIM1` and `TIM8`, and `TIM5` can be used the same way. `TIM5` and
+  `TIM8` can't be remapped, so their `pwm` function doesn't take the `MAPR` register. On the
+  value line, `TIM15` has 2 channels and `TIM16` and `TIM17` have 1 channel, which are remapped
+  through the `MAPR2` register. These 3 timers only count up. The same goes for `TIM9` to `TIM14`
+  of the XL density devices: `TIM9` and `TIM12` have 2 channels, the others have 1 channel.
+  `TIM12` can't be remapped.
+
   ## Usage for pre-defined channel combinations
 
   This crate only defines basic channel combinations for default AFIO remappings,
@@ -51,6 +58,35 @@
     // PWM outputs are disabled by default
     c0.enable()
   ```
+
+  ## Center-aligned mode and polarity
+
+  The counter counts up by default, and the outputs are active high. `with_alignment` selects
+  down-counting or one of the center-aligned modes, in which the counter counts up and down and
+  the pulses of all channels are centered in the period. `set_polarity` inverts a channel:
+
+  ```rust
+  let mut pwm = Timer::tim3(p.TIM3, &clocks)
+      .pwm::<Tim3NoRemap, _, _, _>(pins, &mut afio.mapr, 20.khz())
+      .with_alignment(Alignment::Center1);
+  pwm.set_polarity(Channel::C2, Polarity::ActiveLow);
+  ```
+
+  ## Complementary outputs
+
+  The advanced control timer `TIM1` has complementary outputs for channels 1 to 3, with a dead
+  time between the two outputs of a channel, and a break input which disables all the outputs,
+  e.g. for a half-bridge driver. These are configured before splitting the channels:
+
+  ```rust
+  let mut pwm = Timer::tim1(p.TIM1, &clocks)
+      .pwm::<Tim1NoRemap, _, _, _>(pa8, &mut afio.mapr, 20.khz())
+      .with_complementary(pb13);
+  pwm.set_dead_time(500);
+  pwm.enable_break(pb12, Polarity::ActiveLow);
+  pwm.enable(Channel::C1);
+  pwm.enable_complementary(Channel::C1);
+  ```
 */
 
 use core::marker::Copy;
@@ -61,17 +97,31 @@ use crate::hal;
 use crate::pac::TIM1;
 #[cfg(feature = "medium")]
 use crate::pac::TIM4;
+#[cfg(any(feature = "high", feature = "connectivity"))]
+use crate::pac::TIM5;
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+use crate::pac::TIM8;
+#[cfg(feature = "xl")]
+use crate::pac::{TIM10, TIM11, TIM12, TIM13, TIM14, TIM9};
+#[cfg(feature = "stm32f100")]
+use crate::pac::{TIM15, TIM16, TIM17};
 use crate::pac::{TIM2, TIM3};
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+use cast::u64;
 use cast::{u16, u32};
 
 use crate::afio::MAPR;
+#[cfg(any(feature = "stm32f100", feature = "xl"))]
+use crate::afio::MAPR2;
 use crate::bb;
+use crate::capture::InputMode;
 use crate::gpio::{self, Alternate, PushPull};
 use crate::time::Hertz;
 use crate::time::U32Ext;
 use crate::timer::Timer;
 
-pub trait Pins<REMAP, P> {
+/// Pins of the channels of a timer, `W` selects the type of the channels returned by `split`
+pub trait Pins<REMAP, P, W = PwmChannels> {
     const C1: bool = false;
     const C2: bool = false;
     const C3: bool = false;
@@ -101,20 +151,64 @@ pub enum Channel {
     C4,
 }
 
+/// Output polarity
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Polarity {
+    ActiveHigh,
+    ActiveLow,
+}
+
+/// Counting mode of the timer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alignment {
+    /// Edge-aligned, counting up
+    Edge,
+    /// Edge-aligned, counting down
+    EdgeDown,
+    /// Center-aligned, the compare interrupt flags are set while counting down
+    Center1,
+    /// Center-aligned, the compare interrupt flags are set while counting up
+    Center2,
+    /// Center-aligned, the compare interrupt flags are set while counting up and down
+    Center3,
+}
+
+/// Creates the channel `C` of `TIM` for [`Pins::split`]
+pub trait ChannelWrapper<TIM, C> {
+    type Channel;
+
+    fn channel() -> Self::Channel;
+}
+
+/// Selects [`PwmChannel`] as the channels of [`Pins`]
+pub struct PwmChannels;
+
+impl<TIM, C> ChannelWrapper<TIM, C> for PwmChannels {
+    type Channel = PwmChannel<TIM, C>;
+
+    fn channel() -> Self::Channel {
+        PwmChannel {
+            _channel: PhantomData,
+            _tim: PhantomData,
+        }
+    }
+}
+
 use crate::timer::sealed::{Ch1, Ch2, Ch3, Ch4, Remap};
 macro_rules! pins_impl {
     ( $( ( $($PINX:ident),+ ), ( $($TRAIT:ident),+ ), ( $($ENCHX:ident),+ ); )+ ) => {
         $(
             #[allow(unused_parens)]
-            impl<TIM, REMAP, $($PINX,)+> Pins<REMAP, ($($ENCHX),+)> for ($($PINX),+)
+            impl<TIM, REMAP, W, $($PINX,)+> Pins<REMAP, ($($ENCHX),+), W> for ($($PINX),+)
             where
                 REMAP: Remap<Periph = TIM>,
+                $(W: ChannelWrapper<TIM, $ENCHX>,)+
                 $($PINX: $TRAIT<REMAP> + gpio::PinExt<Mode=Alternate<PushPull>>,)+
             {
                 $(const $ENCHX: bool = true;)+
-                type Channels = ($(PwmChannel<TIM, $ENCHX>),+);
+                type Channels = ($(<W as ChannelWrapper<TIM, $ENCHX>>::Channel),+);
                 fn split() -> Self::Channels {
-                    ($(PwmChannel::<TIM, $ENCHX> { _channel: PhantomData, _tim: PhantomData }),+)
+                    ($(<W as ChannelWrapper<TIM, $ENCHX>>::channel()),+)
                 }
             }
         )+
@@ -139,6 +233,61 @@ pins_impl!(
     (P4), (Ch4), (C4);
 );
 
+/// Pins of the complementary outputs of the advanced control timers
+pub trait ComplementaryPins<REMAP, P> {
+    const C1N: bool = false;
+    const C2N: bool = false;
+    const C3N: bool = false;
+}
+
+use crate::timer::sealed::{Bkin, Ch1N, Ch2N, Ch3N};
+macro_rules! complementary_pins_impl {
+    ( $( ( $($PINX:ident),+ ), ( $($TRAIT:ident),+ ), ( $($ENCHX:ident),+ ), ( $($CHX:ident),+ ); )+ ) => {
+        $(
+            #[allow(unused_parens)]
+            impl<REMAP, $($PINX,)+> ComplementaryPins<REMAP, ($($CHX),+)> for ($($PINX),+)
+            where
+                $($PINX: $TRAIT<REMAP> + gpio::PinExt<Mode=Alternate<PushPull>>,)+
+            {
+                $(const $ENCHX: bool = true;)+
+            }
+        )+
+    };
+}
+
+complementary_pins_impl!(
+    (P1, P2, P3), (Ch1N, Ch2N, Ch3N), (C1N, C2N, C3N), (C1, C2, C3);
+    (P2, P3), (Ch2N, Ch3N), (C2N, C3N), (C2, C3);
+    (P1, P3), (Ch1N, Ch3N), (C1N, C3N), (C1, C3);
+    (P1, P2), (Ch1N, Ch2N), (C1N, C2N), (C1, C2);
+    (P1), (Ch1N), (C1N), (C1);
+    (P2), (Ch2N), (C2N), (C2);
+    (P3), (Ch3N), (C3N), (C3);
+);
+
+/// Break input of the advanced control timers, which disables the outputs when active
+pub trait BreakPin<REMAP> {}
+
+impl<REMAP, P> BreakPin<REMAP> for P
+where
+    P: Bkin<REMAP> + gpio::PinExt,
+    <P as gpio::PinExt>::Mode: InputMode,
+{
+}
+
+/// Write protection of the break, dead time and output configuration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LockLevel {
+    /// No write protection
+    Off = 0,
+    /// The dead time, the break configuration and the automatic output enable are locked
+    Level1 = 1,
+    /// Level 1, and the off-state and idle configuration are locked
+    Level2 = 2,
+    /// Level 2, and the output polarity and the output compare mode are locked
+    Level3 = 3,
+}
+
 #[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
 impl Timer<TIM1> {
     pub fn pwm<REMAP, P, PINS, T>(
@@ -221,12 +370,237 @@ impl Timer<TIM4> {
     }
 }
 
+#[cfg(any(feature = "high", feature = "connectivity"))]
+impl Timer<TIM5> {
+    pub fn pwm<REMAP, P, PINS, T>(self, _pins: PINS, freq: T) -> Pwm<TIM5, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM5>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        let Self { tim, clk } = self;
+        tim5(tim, _pins, freq.into(), clk)
+    }
+}
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+impl Timer<TIM8> {
+    pub fn pwm<REMAP, P, PINS, T>(self, _pins: PINS, freq: T) -> Pwm<TIM8, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM8>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        // TIM8 has a break function that deactivates the outputs, this bit automatically activates
+        // the output when no break input is present
+        self.tim.bdtr.modify(|_, w| w.aoe().set_bit());
+
+        let Self { tim, clk } = self;
+        tim8(tim, _pins, freq.into(), clk)
+    }
+}
+
+#[cfg(feature = "stm32f100")]
+impl Timer<TIM15> {
+    pub fn pwm<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr2: &mut MAPR2,
+        freq: T,
+    ) -> Pwm<TIM15, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM15>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr2
+            .mapr2()
+            .modify(|_, w| w.tim15_remap().bit(REMAP::REMAP == 1));
+        self.tim.bdtr.modify(|_, w| w.aoe().set_bit());
+
+        let Self { tim, clk } = self;
+        tim15(tim, _pins, freq.into(), clk)
+    }
+}
+
+#[cfg(feature = "stm32f100")]
+impl Timer<TIM16> {
+    pub fn pwm<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr2: &mut MAPR2,
+        freq: T,
+    ) -> Pwm<TIM16, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM16>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr2
+            .mapr2()
+            .modify(|_, w| w.tim16_remap().bit(REMAP::REMAP == 1));
+        self.tim.bdtr.modify(|_, w| w.aoe().set_bit());
+
+        let Self { tim, clk } = self;
+        tim16(tim, _pins, freq.into(), clk)
+    }
+}
+
+#[cfg(feature = "stm32f100")]
+impl Timer<TIM17> {
+    pub fn pwm<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr2: &mut MAPR2,
+        freq: T,
+    ) -> Pwm<TIM17, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM17>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr2
+            .mapr2()
+            .modify(|_, w| w.tim17_remap().bit(REMAP::REMAP == 1));
+        self.tim.bdtr.modify(|_, w| w.aoe().set_bit());
+
+        let Self { tim, clk } = self;
+        tim17(tim, _pins, freq.into(), clk)
+    }
+}
+
+#[cfg(feature = "xl")]
+impl Timer<TIM9> {
+    pub fn pwm<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr2: &mut MAPR2,
+        freq: T,
+    ) -> Pwm<TIM9, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM9>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr2
+            .mapr2()
+            .modify(|_, w| w.tim9_remap().bit(REMAP::REMAP == 1));
+
+        let Self { tim, clk } = self;
+        tim9(tim, _pins, freq.into(), clk)
+    }
+}
+
+#[cfg(feature = "xl")]
+impl Timer<TIM10> {
+    pub fn pwm<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr2: &mut MAPR2,
+        freq: T,
+    ) -> Pwm<TIM10, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM10>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr2
+            .mapr2()
+            .modify(|_, w| w.tim10_remap().bit(REMAP::REMAP == 1));
+
+        let Self { tim, clk } = self;
+        tim10(tim, _pins, freq.into(), clk)
+    }
+}
+
+#[cfg(feature = "xl")]
+impl Timer<TIM11> {
+    pub fn pwm<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr2: &mut MAPR2,
+        freq: T,
+    ) -> Pwm<TIM11, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM11>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr2
+            .mapr2()
+            .modify(|_, w| w.tim11_remap().bit(REMAP::REMAP == 1));
+
+        let Self { tim, clk } = self;
+        tim11(tim, _pins, freq.into(), clk)
+    }
+}
+
+#[cfg(feature = "xl")]
+impl Timer<TIM12> {
+    pub fn pwm<REMAP, P, PINS, T>(self, _pins: PINS, freq: T) -> Pwm<TIM12, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM12>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        let Self { tim, clk } = self;
+        tim12(tim, _pins, freq.into(), clk)
+    }
+}
+
+#[cfg(feature = "xl")]
+impl Timer<TIM13> {
+    pub fn pwm<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr2: &mut MAPR2,
+        freq: T,
+    ) -> Pwm<TIM13, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM13>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr2
+            .mapr2()
+            .modify(|_, w| w.tim13_remap().bit(REMAP::REMAP == 1));
+
+        let Self { tim, clk } = self;
+        tim13(tim, _pins, freq.into(), clk)
+    }
+}
+
+#[cfg(feature = "xl")]
+impl Timer<TIM14> {
+    pub fn pwm<REMAP, P, PINS, T>(
+        self,
+        _pins: PINS,
+        mapr2: &mut MAPR2,
+        freq: T,
+    ) -> Pwm<TIM14, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM14>,
+        PINS: Pins<REMAP, P>,
+        T: Into<Hertz>,
+    {
+        mapr2
+            .mapr2()
+            .modify(|_, w| w.tim14_remap().bit(REMAP::REMAP == 1));
+
+        let Self { tim, clk } = self;
+        tim14(tim, _pins, freq.into(), clk)
+    }
+}
+
 pub struct Pwm<TIM, REMAP, P, PINS>
 where
     REMAP: Remap<Periph = TIM>,
     PINS: Pins<REMAP, P>,
 {
     clk: Hertz,
+    /// Channels with complementary outputs, one bit per channel
+    #[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+    complementary: u8,
     _pins: PhantomData<(TIM, REMAP, P, PINS)>,
 }
 
@@ -307,6 +681,8 @@ macro_rules! hal {
 
                 Pwm {
                     clk,
+                    #[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+                    complementary: 0,
                     _pins: PhantomData
                 }
             }
@@ -377,15 +753,15 @@ macro_rules! hal {
                     let psc: u16 = unsafe{(*$TIMX::ptr()).psc.read().psc().bits()};
                     let arr: u16 = unsafe{(*$TIMX::ptr()).arr.read().arr().bits()};
 
-                    // Length in ms of an internal clock pulse
-                    (clk.0 / u32(psc * arr)).hz()
+                    // A center-aligned counter counts up and down in one period
+                    (clk.0 / u32(psc + 1) / u32(arr) / Self::counts_per_period()).hz()
                 }
 
                 fn set_period<T>(&mut self, period: T) where
                     T: Into<Self::Time> {
                         let clk = self.clk;
 
-                        let ticks = clk.0 / period.into().0;
+                        let ticks = clk.0 / period.into().0 / Self::counts_per_period();
                         let psc = u16(ticks / (1 << 16)).unwrap();
                         let arr = u16(ticks / u32(psc + 1)).unwrap();
                         unsafe {
@@ -395,6 +771,57 @@ macro_rules! hal {
                 }
             }
 
+            impl<REMAP, P, PINS> Pwm<$TIMX, REMAP, P, PINS> where
+                REMAP: Remap<Periph = $TIMX>,
+                PINS: Pins<REMAP, P>,
+            {
+                fn counts_per_period() -> u32 {
+                    if unsafe { (*$TIMX::ptr()).cr1.read().cms().bits() } == 0 {
+                        1
+                    } else {
+                        2
+                    }
+                }
+
+                /// Changes the counting mode, keeping the PWM frequency. The maximum duty is halved
+                /// in center-aligned mode, as the counter counts up and down in each period.
+                pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+                    let period = hal::Pwm::get_period(&self);
+                    let tim = unsafe { &*$TIMX::ptr() };
+
+                    // The counter must be stopped to switch between edge and center alignment
+                    tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    let (cms, dir) = match alignment {
+                        Alignment::Edge => (0b00, false),
+                        Alignment::EdgeDown => (0b00, true),
+                        Alignment::Center1 => (0b01, false),
+                        Alignment::Center2 => (0b10, false),
+                        Alignment::Center3 => (0b11, false),
+                    };
+                    tim.cr1.modify(|_, w| w.cms().bits(cms).dir().bit(dir));
+                    hal::Pwm::set_period(&mut self, period);
+
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+                    tim.egr.write(|w| w.ug().set_bit());
+                    tim.cr1.modify(|_, w| w.urs().clear_bit());
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+                    self
+                }
+
+                /// Sets the level of the output of a channel while it is active
+                pub fn set_polarity(&mut self, channel: Channel, polarity: Polarity) {
+                    let bit = match PINS::check_used(channel) {
+                        Channel::C1 => 1,
+                        Channel::C2 => 5,
+                        Channel::C3 => 9,
+                        Channel::C4 => 13,
+                    };
+                    unsafe {
+                        bb::write(&(*$TIMX::ptr()).ccer, bit, polarity == Polarity::ActiveLow)
+                    }
+                }
+            }
+
             impl hal::PwmPin for PwmChannel<$TIMX, C1> {
                 type Duty = u16;
 
@@ -490,6 +917,34 @@ macro_rules! hal {
                     unsafe { (*$TIMX::ptr()).ccr4.write(|w| w.ccr().bits(duty)) }
                 }
             }
+
+            impl PwmChannel<$TIMX, C1> {
+                /// Sets the level of the output while it is active
+                pub fn set_polarity(&mut self, polarity: Polarity) {
+                    unsafe { bb::write(&(*$TIMX::ptr()).ccer, 1, polarity == Polarity::ActiveLow) }
+                }
+            }
+
+            impl PwmChannel<$TIMX, C2> {
+                /// Sets the level of the output while it is active
+                pub fn set_polarity(&mut self, polarity: Polarity) {
+                    unsafe { bb::write(&(*$TIMX::ptr()).ccer, 5, polarity == Polarity::ActiveLow) }
+                }
+            }
+
+            impl PwmChannel<$TIMX, C3> {
+                /// Sets the level of the output while it is active
+                pub fn set_polarity(&mut self, polarity: Polarity) {
+                    unsafe { bb::write(&(*$TIMX::ptr()).ccer, 9, polarity == Polarity::ActiveLow) }
+                }
+            }
+
+            impl PwmChannel<$TIMX, C4> {
+                /// Sets the level of the output while it is active
+                pub fn set_polarity(&mut self, polarity: Polarity) {
+                    unsafe { bb::write(&(*$TIMX::ptr()).ccer, 13, polarity == Polarity::ActiveLow) }
+                }
+            }
         )+
     }
 }
@@ -508,3 +963,444 @@ hal! {
 hal! {
     TIM4: (tim4),
 }
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+hal! {
+    TIM5: (tim5),
+}
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+hal! {
+    TIM8: (tim8),
+}
+
+// TIM9 to TIM17 only count up and have less channels
+#[cfg(any(feature = "stm32f100", feature = "xl"))]
+macro_rules! hal_channels {
+    ($($TIMX:ident: ($timX:ident, [$($C:ident: ($ocpe:ident, $ocm:ident, $ccr:ident, $ccrx:ident, $bit:literal),)+]),)+) => {
+        $(
+            fn $timX<REMAP, P, PINS>(
+                tim: $TIMX,
+                _pins: PINS,
+                freq: Hertz,
+                clk: Hertz,
+            ) -> Pwm<$TIMX, REMAP, P, PINS>
+            where
+                REMAP: Remap<Periph = $TIMX>,
+                PINS: Pins<REMAP, P>,
+            {
+                $(
+                    if PINS::$C {
+                        tim.ccmr1_output()
+                            .modify(|_, w| unsafe { w.$ocpe().set_bit().$ocm().bits(0b110) });
+                    }
+                )+
+                let ticks = clk.0 / freq.0;
+                let psc = u16(ticks / (1 << 16)).unwrap();
+                tim.psc.write(|w| w.psc().bits(psc));
+                let arr = u16(ticks / u32(psc + 1)).unwrap();
+                tim.arr.write(|w| unsafe { w.arr().bits(arr) });
+
+                // The psc register is buffered, so we trigger an update event to update it
+                // Sets the URS bit to prevent an interrupt from being triggered by the UG bit
+                tim.cr1.modify(|_, w| w.urs().set_bit());
+                tim.egr.write(|w| w.ug().set_bit());
+                tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                tim.cr1.write(|w| w.cen().set_bit());
+
+                Pwm {
+                    clk,
+                    #[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+                    complementary: 0,
+                    _pins: PhantomData,
+                }
+            }
+
+            impl<REMAP, P, PINS> hal::Pwm for Pwm<$TIMX, REMAP, P, PINS>
+            where
+                REMAP: Remap<Periph = $TIMX>,
+                PINS: Pins<REMAP, P>,
+            {
+                type Channel = Channel;
+                type Duty = u16;
+                type Time = Hertz;
+
+                fn enable(&mut self, channel: Self::Channel) {
+                    match PINS::check_used(channel) {
+                        $(Channel::$C => unsafe { bb::set(&(*$TIMX::ptr()).ccer, $bit) },)+
+                        _ => unreachable!(),
+                    }
+                }
+
+                fn disable(&mut self, channel: Self::Channel) {
+                    match PINS::check_used(channel) {
+                        $(Channel::$C => unsafe { bb::clear(&(*$TIMX::ptr()).ccer, $bit) },)+
+                        _ => unreachable!(),
+                    }
+                }
+
+                fn get_duty(&self, channel: Self::Channel) -> Self::Duty {
+                    match PINS::check_used(channel) {
+                        $(Channel::$C => unsafe { (*$TIMX::ptr()).$ccr.read().$ccrx().bits() },)+
+                        _ => unreachable!(),
+                    }
+                }
+
+                fn set_duty(&mut self, channel: Self::Channel, duty: Self::Duty) {
+                    match PINS::check_used(channel) {
+                        $(Channel::$C => unsafe {
+                            (*$TIMX::ptr()).$ccr.write(|w| w.$ccrx().bits(duty))
+                        },)+
+                        _ => unreachable!(),
+                    }
+                }
+
+                fn get_max_duty(&self) -> Self::Duty {
+                    unsafe { (*$TIMX::ptr()).arr.read().arr().bits() }
+                }
+
+                fn get_period(&self) -> Self::Time {
+                    let clk = self.clk;
+                    let psc: u16 = unsafe { (*$TIMX::ptr()).psc.read().psc().bits() };
+                    let arr: u16 = unsafe { (*$TIMX::ptr()).arr.read().arr().bits() };
+
+                    (clk.0 / u32(psc + 1) / u32(arr)).hz()
+                }
+
+                fn set_period<T>(&mut self, period: T)
+                where
+                    T: Into<Self::Time>,
+                {
+                    let clk = self.clk;
+
+                    let ticks = clk.0 / period.into().0;
+                    let psc = u16(ticks / (1 << 16)).unwrap();
+                    let arr = u16(ticks / u32(psc + 1)).unwrap();
+                    unsafe {
+                        (*$TIMX::ptr()).psc.write(|w| w.psc().bits(psc));
+                        (*$TIMX::ptr()).arr.write(|w| w.arr().bits(arr));
+                    }
+                }
+            }
+
+            impl<REMAP, P, PINS> Pwm<$TIMX, REMAP, P, PINS>
+            where
+                REMAP: Remap<Periph = $TIMX>,
+                PINS: Pins<REMAP, P>,
+            {
+                /// Sets the level of the output of a channel while it is active
+                pub fn set_polarity(&mut self, channel: Channel, polarity: Polarity) {
+                    let bit = match PINS::check_used(channel) {
+                        $(Channel::$C => $bit + 1,)+
+                        _ => unreachable!(),
+                    };
+                    unsafe {
+                        bb::write(&(*$TIMX::ptr()).ccer, bit, polarity == Polarity::ActiveLow)
+                    }
+                }
+            }
+
+            $(
+                impl hal::PwmPin for PwmChannel<$TIMX, $C> {
+                    type Duty = u16;
+
+                    fn disable(&mut self) {
+                        unsafe { bb::clear(&(*$TIMX::ptr()).ccer, $bit) }
+                    }
+
+                    fn enable(&mut self) {
+                        unsafe { bb::set(&(*$TIMX::ptr()).ccer, $bit) }
+                    }
+
+                    fn get_duty(&self) -> u16 {
+                        unsafe { (*$TIMX::ptr()).$ccr.read().$ccrx().bits() }
+                    }
+
+                    fn get_max_duty(&self) -> u16 {
+                        unsafe { (*$TIMX::ptr()).arr.read().arr().bits() }
+                    }
+
+                    fn set_duty(&mut self, duty: u16) {
+                        unsafe { (*$TIMX::ptr()).$ccr.write(|w| w.$ccrx().bits(duty)) }
+                    }
+                }
+
+                impl PwmChannel<$TIMX, $C> {
+                    /// Sets the level of the output while it is active
+                    pub fn set_polarity(&mut self, polarity: Polarity) {
+                        unsafe {
+                            bb::write(&(*$TIMX::ptr()).ccer, $bit + 1, polarity == Polarity::ActiveLow)
+                        }
+                    }
+                }
+            )+
+        )+
+    };
+}
+
+#[cfg(feature = "stm32f100")]
+hal_channels! {
+    TIM15: (tim15, [
+        C1: (oc1pe, oc1m, ccr1, ccr1, 0),
+        C2: (oc2pe, oc2m, ccr2, ccr2, 4),
+    ]),
+    TIM16: (tim16, [
+        C1: (oc1pe, oc1m, ccr1, ccr1, 0),
+    ]),
+    TIM17: (tim17, [
+        C1: (oc1pe, oc1m, ccr1, ccr1, 0),
+    ]),
+}
+
+#[cfg(feature = "xl")]
+hal_channels! {
+    TIM9: (tim9, [
+        C1: (oc1pe, oc1m, ccr1, ccr, 0),
+        C2: (oc2pe, oc2m, ccr2, ccr, 4),
+    ]),
+    TIM10: (tim10, [
+        C1: (oc1pe, oc1m, ccr1, ccr, 0),
+    ]),
+    TIM11: (tim11, [
+        C1: (oc1pe, oc1m, ccr1, ccr, 0),
+    ]),
+    TIM12: (tim12, [
+        C1: (oc1pe, oc1m, ccr1, ccr, 0),
+        C2: (oc2pe, oc2m, ccr2, ccr, 4),
+    ]),
+    TIM13: (tim13, [
+        C1: (oc1pe, oc1m, ccr1, ccr, 0),
+    ]),
+    TIM14: (tim14, [
+        C1: (oc1pe, oc1m, ccr1, ccr, 0),
+    ]),
+}
+
+/// Encodes a dead time of `ticks` timer clock cycles as the CKD and DTG bits
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+fn dead_time_bits(ticks: u32) -> (u8, u8) {
+    let div_ceil = |a: u32, b: u32| if a / b * b < a { a / b + 1 } else { a / b };
+    for ckd in 0..3 {
+        // The dead time generator runs at the timer clock divided by CKD
+        let t = div_ceil(ticks, 1 << ckd);
+        let dtg = if t <= 127 {
+            t
+        } else if div_ceil(t, 2) <= 127 {
+            0x80 | (div_ceil(t, 2) - 64)
+        } else if div_ceil(t, 8) <= 63 {
+            0xc0 | (div_ceil(t, 8) - 32)
+        } else if div_ceil(t, 16) <= 63 {
+            0xe0 | (div_ceil(t, 16) - 32)
+        } else {
+            continue;
+        };
+        return (ckd as u8, dtg as u8);
+    }
+    panic!("Dead time too long")
+}
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+macro_rules! advanced {
+    ($($TIMX:ident: [$($C:ident: ($CN:ident, $bit:literal),)+],)+) => {
+        $(
+            impl<REMAP, P, PINS> Pwm<$TIMX, REMAP, P, PINS>
+            where
+                REMAP: Remap<Periph = $TIMX>,
+                PINS: Pins<REMAP, P>,
+            {
+                /// Takes the pins of the complementary outputs, which are enabled with
+                /// `enable_complementary`
+                pub fn with_complementary<NP, NPINS>(mut self, _npins: NPINS) -> Self
+                where
+                    NPINS: ComplementaryPins<REMAP, NP>,
+                {
+                    $(
+                        if NPINS::$CN {
+                            PINS::check_used(Channel::$C);
+                            self.complementary |= 1 << $bit;
+                        }
+                    )+
+                    self
+                }
+
+                fn check_complementary(&self, channel: Channel) -> u8 {
+                    let bit = match channel {
+                        $(Channel::$C => $bit,)+
+                        _ => panic!("Unused channel"),
+                    };
+                    if self.complementary & (1 << bit) == 0 {
+                        panic!("Unused channel")
+                    }
+                    bit
+                }
+
+                /// Enables the complementary output of a channel, which is inactive while the
+                /// channel output is active
+                pub fn enable_complementary(&mut self, channel: Channel) {
+                    let bit = self.check_complementary(channel);
+                    unsafe { bb::set(&(*$TIMX::ptr()).ccer, 4 * bit + 2) }
+                }
+
+                pub fn disable_complementary(&mut self, channel: Channel) {
+                    let bit = self.check_complementary(channel);
+                    unsafe { bb::clear(&(*$TIMX::ptr()).ccer, 4 * bit + 2) }
+                }
+
+                /// Sets the level of the complementary output of a channel while it is active
+                pub fn set_complementary_polarity(&mut self, channel: Channel, polarity: Polarity) {
+                    let bit = self.check_complementary(channel);
+                    unsafe {
+                        bb::write(
+                            &(*$TIMX::ptr()).ccer,
+                            4 * bit + 3,
+                            polarity == Polarity::ActiveLow,
+                        )
+                    }
+                }
+
+                /// Sets the time in nanoseconds during which both outputs of a channel are
+                /// inactive when switching between them
+                pub fn set_dead_time(&mut self, ns: u32) {
+                    // Rounds up, so that the dead time is never shorter than requested
+                    let ticks = u64(ns) * u64(self.clk.0);
+                    let ticks = ticks / 1_000_000_000 + u64::from(ticks % 1_000_000_000 > 0);
+                    let (ckd, dtg) = dead_time_bits(u32(ticks).unwrap());
+                    let tim = unsafe { &*$TIMX::ptr() };
+                    tim.cr1.modify(|_, w| match ckd {
+                        0 => w.ckd().div1(),
+                        1 => w.ckd().div2(),
+                        _ => w.ckd().div4(),
+                    });
+                    tim.bdtr.modify(|_, w| unsafe { w.dtg().bits(dtg) });
+                }
+
+                /// Disables all the outputs while `pin` is at the `polarity` level
+                pub fn enable_break<BK>(&mut self, _pin: BK, polarity: Polarity)
+                where
+                    BK: BreakPin<REMAP>,
+                {
+                    unsafe { &*$TIMX::ptr() }.bdtr.modify(|_, w| {
+                        w.bkp()
+                            .bit(polarity == Polarity::ActiveHigh)
+                            .bke()
+                            .set_bit()
+                    });
+                }
+
+                pub fn disable_break(&mut self) {
+                    unsafe { &*$TIMX::ptr() }.bdtr.modify(|_, w| w.bke().clear_bit());
+                }
+
+                /// Returns `true` if a break has occurred since the last call
+                pub fn break_occurred(&mut self) -> bool {
+                    let tim = unsafe { &*$TIMX::ptr() };
+                    if tim.sr.read().bif().bit_is_set() {
+                        // Writing 1 has no effect on the other flags
+                        tim.sr.write(|w| unsafe { w.bits(0xffff & !(1 << 7)) });
+                        true
+                    } else {
+                        false
+                    }
+                }
+
+                /// Enables the outputs again at the next update event once the break input is
+                /// inactive. Otherwise, the outputs stay disabled until `enable_outputs` is called.
+                pub fn set_automatic_output(&mut self, automatic: bool) {
+                    unsafe { &*$TIMX::ptr() }.bdtr.modify(|_, w| w.aoe().bit(automatic));
+                }
+
+                /// Sets the main output enable bit
+                pub fn enable_outputs(&mut self) {
+                    unsafe { &*$TIMX::ptr() }.bdtr.modify(|_, w| w.moe().set_bit());
+                }
+
+                /// Clears the main output enable bit, which sets all the outputs inactive
+                pub fn disable_outputs(&mut self) {
+                    unsafe { &*$TIMX::ptr() }.bdtr.modify(|_, w| w.moe().clear_bit());
+                }
+
+                /// Protects the configuration against software errors. The lock level can only be
+                /// written once after a reset.
+                pub fn lock(&mut self, level: LockLevel) {
+                    unsafe { &*$TIMX::ptr() }
+                        .bdtr
+                        .modify(|_, w| unsafe { w.lock().bits(level as u8) });
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+advanced! {
+    TIM1: [
+        C1: (C1N, 0),
+        C2: (C2N, 1),
+        C3: (C3N, 2),
+    ],
+}
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+advanced! {
+    TIM8: [
+        C1: (C1N, 0),
+        C2: (C2N, 1),
+        C3: (C3N, 2),
+    ],
+}
+
+#[cfg(feature = "stm32f100")]
+advanced! {
+    TIM15: [
+        C1: (C1N, 0),
+    ],
+    TIM16: [
+        C1: (C1N, 0),
+    ],
+    TIM17: [
+        C1: (C1N, 0),
+    ],
+}
+
+#[cfg(all(
+    test,
+    any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",)
+))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_time_ranges() {
+        assert_eq!(dead_time_bits(0), (0, 0));
+        assert_eq!(dead_time_bits(127), (0, 127));
+        assert_eq!(dead_time_bits(128), (0, 0x80));
+        assert_eq!(dead_time_bits(254), (0, 0xbf));
+        assert_eq!(dead_time_bits(255), (0, 0xc0));
+        assert_eq!(dead_time_bits(504), (0, 0xdf));
+        assert_eq!(dead_time_bits(505), (0, 0xe0));
+        assert_eq!(dead_time_bits(1008), (0, 0xff));
+    }
+
+    #[test]
+    fn dead_time_rounds_up() {
+        // 130 ticks in steps of 2
+        assert_eq!(dead_time_bits(129), (0, 0x81));
+        // 264 ticks in steps of 8
+        assert_eq!(dead_time_bits(257), (0, 0xc1));
+    }
+
+    #[test]
+    fn dead_time_clock_division() {
+        assert_eq!(dead_time_bits(1009), (1, 0xe0));
+        assert_eq!(dead_time_bits(2016), (1, 0xff));
+        assert_eq!(dead_time_bits(2017), (2, 0xe0));
+        assert_eq!(dead_time_bits(4032), (2, 0xff));
+    }
+
+    #[test]
+    #[should_panic(expected = "Dead time too long")]
+    fn dead_time_too_long() {
+        dead_time_bits(4033);
+    }
+}
@@ -9,9 +9,13 @@ use crate::pac::DBGMCU as DBG;
 use crate::pac::TIM1;
 #[cfg(feature = "medium")]
 use crate::pac::TIM4;
+#[cfg(feature = "xl")]
+use crate::pac::{TIM12, TIM9};
 use crate::pac::{TIM2, TIM3};
 
 use crate::afio::MAPR;
+#[cfg(feature = "xl")]
+use crate::afio::MAPR2;
 use crate::gpio::{self, Floating, Input};
 use crate::rcc::{Clocks, GetBusFreq, RccBus};
 use crate::time::Hertz;
@@ -164,6 +168,46 @@ impl Timer<TIM4> {
     }
 }
 
+// The debug freeze bits of TIM9 and TIM12 are missing from the device crate, so these two don't
+// take the `DBGMCU` register. TIM12 can't be remapped.
+#[cfg(feature = "xl")]
+impl Timer<TIM9> {
+    pub fn pwm_input<REMAP, PINS, T>(
+        self,
+        pins: PINS,
+        mapr2: &mut MAPR2,
+        mode: Configuration<T>,
+    ) -> PwmInput<TIM9, REMAP, PINS>
+    where
+        REMAP: Remap<Periph = TIM9>,
+        PINS: Pins<REMAP>,
+        T: Into<Hertz>,
+    {
+        mapr2
+            .mapr2()
+            .modify(|_, w| w.tim9_remap().bit(REMAP::REMAP == 1));
+        let Self { tim, clk } = self;
+        tim9(tim, pins, clk, mode)
+    }
+}
+
+#[cfg(feature = "xl")]
+impl Timer<TIM12> {
+    pub fn pwm_input<REMAP, PINS, T>(
+        self,
+        pins: PINS,
+        mode: Configuration<T>,
+    ) -> PwmInput<TIM12, REMAP, PINS>
+    where
+        REMAP: Remap<Periph = TIM12>,
+        PINS: Pins<REMAP>,
+        T: Into<Hertz>,
+    {
+        let Self { tim, clk } = self;
+        tim12(tim, pins, clk, mode)
+    }
+}
+
 /// Courtesy of @TeXitoi (https://github.com/stm32-rs/stm32f1xx-hal/pull/10#discussion_r259535503)
 fn compute_arr_presc(freq: u32, clock: u32) -> (u16, u16) {
     if freq == 0 {
@@ -173,9 +217,28 @@ fn compute_arr_presc(freq: u32, clock: u32) -> (u16, u16) {
     let arr = clock / freq.saturating_mul(presc + 1);
     (core::cmp::max(1, arr as u16), presc as u16)
 }
+/// Makes both channels capture TI1
+macro_rules! select_ti1 {
+    // The capture selection fields of these timers have no enumerated values
+    (TIM9, $tim:ident) => {
+        select_ti1!(@bits $tim)
+    };
+    (TIM12, $tim:ident) => {
+        select_ti1!(@bits $tim)
+    };
+    (@bits $tim:ident) => {
+        $tim.ccmr1_input().modify( |_,w| unsafe { w.cc1s().bits(0b01).cc2s().bits(0b10) })
+    };
+    ($TIMX:ident, $tim:ident) => {
+        $tim.ccmr1_input().modify( |_,w| w.cc1s().ti1().cc2s().ti1())
+    };
+}
+
 macro_rules! hal {
     ($($TIMX:ident: ($timX:ident),)+) => {
         $(
+            // TODO: Remove this `allow` once the arr field is made safe for TIM9 and TIM12
+            #[allow(unused_unsafe)]
             fn $timX<REMAP, PINS,T>(
                 tim: $TIMX,
                 _pins: PINS,
@@ -195,7 +258,7 @@ macro_rules! hal {
 
                 // Define the direction of the channel (input/output)
                 // and the used input
-                tim.ccmr1_input().modify( |_,w| w.cc1s().ti1().cc2s().ti1());
+                select_ti1!($TIMX, tim);
 
                 tim.dier.write(|w| w.cc1ie().set_bit());
 
@@ -213,24 +276,24 @@ macro_rules! hal {
                         let freq = f.into().0;
                         let max_freq = if freq > 5 {freq/5} else {1};
                         let (arr,presc) = compute_arr_presc(max_freq, clk.0);
-                        tim.arr.write(|w| w.arr().bits(arr));
+                        tim.arr.write(|w| unsafe { w.arr().bits(arr) });
                         tim.psc.write(|w| w.psc().bits(presc) );
                     },
                     DutyCycle(f) => {
                         let freq = f.into().0;
                         let max_freq = if freq > 2 {freq/2 + freq/4 + freq/8} else {1};
                         let (arr,presc) = compute_arr_presc(max_freq, clk.0);
-                        tim.arr.write(|w| w.arr().bits(arr));
+                        tim.arr.write(|w| unsafe { w.arr().bits(arr) });
                         tim.psc.write(|w| w.psc().bits(presc) );
                     },
                     RawFrequency(f) => {
                         let freq = f.into().0;
                         let (arr,presc) = compute_arr_presc(freq, clk.0);
-                        tim.arr.write(|w| w.arr().bits(arr));
+                        tim.arr.write(|w| unsafe { w.arr().bits(arr) });
                         tim.psc.write(|w| w.psc().bits(presc) );
                     }
                     RawValues{arr, presc} => {
-                        tim.arr.write(|w| w.arr().bits(arr));
+                        tim.arr.write(|w| unsafe { w.arr().bits(arr) });
                         tim.psc.write(|w| w.psc().bits(presc) );
                     }
                 }
@@ -319,3 +382,9 @@ hal! {
 hal! {
     TIM4: (tim4),
 }
+
+#[cfg(feature = "xl")]
+hal! {
+    TIM9: (tim9),
+    TIM12: (tim12),
+}
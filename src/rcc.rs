@@ -28,6 +28,8 @@ impl RccExt for RCC {
                 pclk2: None,
                 sysclk: None,
                 adcclk: None,
+                #[cfg(feature = "connectivity")]
+                i2sclk: None,
             },
             bkp: BKP { _0: () },
         }
@@ -115,6 +117,8 @@ pub struct CFGR {
     pclk2: Option<u32>,
     sysclk: Option<u32>,
     adcclk: Option<u32>,
+    #[cfg(feature = "connectivity")]
+    i2sclk: Option<u32>,
 }
 
 impl CFGR {
@@ -174,6 +178,19 @@ impl CFGR {
         self
     }
 
+    /// Sets the desired frequency for the I2S2 and I2S3 clock
+    ///
+    /// The clock is generated by PLL3 from the HSE oscillator, so [use_hse](#method.use_hse) has
+    /// to be set as well. Without this setting the I2S peripherals are clocked by SYSCLK.
+    #[cfg(feature = "connectivity")]
+    pub fn i2sclk<F>(mut self, freq: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.i2sclk = Some(freq.into().0);
+        self
+    }
+
     /// Applies the clock configuration and returns a `Clocks` struct that signifies that the
     /// clocks are frozen, and contains the frequencies used. After this function is called,
     /// the clocks can not change
@@ -302,6 +319,17 @@ impl CFGR {
 
         assert!(adcclk <= 14_000_000);
 
+        #[cfg(feature = "connectivity")]
+        let pll3 = self.i2sclk.map(|i2sclk| {
+            let hse = self.hse.expect("the I2S clock requires HSE");
+            pll3_config(hse, i2sclk)
+        });
+
+        #[cfg(feature = "connectivity")]
+        let i2sclk = pll3.map(|(_, _, i2sclk)| i2sclk).unwrap_or(sysclk);
+        #[cfg(not(feature = "connectivity"))]
+        let i2sclk = sysclk;
+
         let rcc = unsafe { &*RCC::ptr() };
 
         if self.hse.is_some() {
@@ -328,6 +356,25 @@ impl CFGR {
             while rcc.cr.read().pllrdy().bit_is_clear() {}
         }
 
+        #[cfg(feature = "connectivity")]
+        if let Some((prediv2, pll3mul_bits, _)) = pll3 {
+            // enable PLL3 and wait for it to be ready, then clock both I2S from its VCO output
+            rcc.cfgr2.modify(|_, w| unsafe {
+                w.prediv2()
+                    .bits(prediv2 - 1)
+                    .pll3mul()
+                    .bits(pll3mul_bits)
+                    .i2s2src()
+                    .pll3()
+                    .i2s3src()
+                    .pll3()
+            });
+
+            rcc.cr.modify(|_, w| w.pll3on().set_bit());
+
+            while rcc.cr.read().pll3rdy().bit_is_clear() {}
+        }
+
         // set prescalers and clock source
         #[cfg(feature = "connectivity")]
         rcc.cfgr.modify(|_, w| unsafe {
@@ -407,11 +454,55 @@ impl CFGR {
             ppre2,
             sysclk: Hertz(sysclk),
             adcclk: Hertz(adcclk),
+            i2sclk: Hertz(i2sclk),
             usbclk_valid,
         }
     }
 }
 
+/// Finds the PLL3 setting whose VCO output is closest to `i2sclk`
+///
+/// Returns the PREDIV2 divider, the PLL3MUL bits and the resulting VCO frequency.
+#[cfg(feature = "connectivity")]
+fn pll3_config(hse: u32, i2sclk: u32) -> (u8, u8, u32) {
+    // multiplication factor and its PLL3MUL bits
+    const PLL3MUL: [(u32, u8); 9] = [
+        (8, 6),
+        (9, 7),
+        (10, 8),
+        (11, 9),
+        (12, 10),
+        (13, 11),
+        (14, 12),
+        (16, 14),
+        (20, 15),
+    ];
+
+    let mut best = None;
+    let mut best_error = u32::MAX;
+    for prediv2 in 1..=16u8 {
+        let pll3_in = hse / u32(prediv2);
+        // the PLL3 input has to be between 3 and 5 MHz
+        if !(3_000_000..=5_000_000).contains(&pll3_in) {
+            continue;
+        }
+        for &(mul, bits) in PLL3MUL.iter() {
+            let pll3clk = pll3_in * mul;
+            if !(40_000_000..=74_000_000).contains(&pll3clk) {
+                continue;
+            }
+            // the I2S is clocked by the VCO, which runs at twice the PLL3 output
+            let vco = 2 * pll3clk;
+            let error = cmp::max(vco, i2sclk) - cmp::min(vco, i2sclk);
+            if error < best_error {
+                best = Some((prediv2, bits, vco));
+                best_error = error;
+            }
+        }
+    }
+    best.expect("no PLL3 setting for this HSE frequency")
+}
+
 pub struct BKP {
     _0: (),
 }
@@ -453,6 +544,7 @@ pub struct Clocks {
     ppre2: u8,
     sysclk: Hertz,
     adcclk: Hertz,
+    i2sclk: Hertz,
     usbclk_valid: bool,
 }
 
@@ -502,6 +594,11 @@ impl Clocks {
         self.adcclk
     }
 
+    /// Returns the clock frequency of the I2S2 and I2S3 peripherals
+    pub fn i2sclk(&self) -> Hertz {
+        self.i2sclk
+    }
+
     /// Returns whether the USBCLK clock frequency is valid for the USB peripheral
     pub fn usbclk_valid(&self) -> bool {
         self.usbclk_valid
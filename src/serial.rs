@@ -57,6 +57,16 @@ use crate::gpio::{Alternate, Floating, Input, PushPull};
 use crate::rcc::{Clocks, Enable, GetBusFreq, Reset};
 use crate::time::{Bps, U32Ext};
 
+pub mod autobaud;
+pub mod buffered;
+pub use buffered::BufferedSerial;
+pub mod frame;
+pub use frame::FrameReader;
+pub mod rs485;
+pub use rs485::Rs485;
+pub mod usart_spi;
+pub use usart_spi::UsartSpi;
+
 /// Interrupt event
 pub enum Event {
     /// New data has been received
@@ -79,6 +89,8 @@ pub enum Error {
     Overrun,
     /// Parity check error
     Parity,
+    /// No data has been received in time
+    Timeout,
 }
 
 // USART REMAPPING, see: https://www.st.com/content/ccc/resource/technical/document/reference_manual/59/b9/ba/7f/11/af/43/d5/CD00171190.pdf/files/CD00171190.pdf/jcr:content/translations/en.CD00171190.pdf
@@ -132,10 +144,53 @@ pub enum StopBits {
     STOP1P5,
 }
 
+/// Number of data bits in a word, not counting the parity bit
+pub enum WordLength {
+    /// 8 data bits
+    DataBits8,
+    /// 9 data bits. Use `Read<u16>` and `Write<u16>`, or `read9` and `write9`, to transfer the
+    /// words. Not available together with parity.
+    DataBits9,
+}
+
+/// Method used to wake up the receiver from mute mode
+///
+/// Mute mode is used for multiprocessor communication on a multi-drop bus. It is entered with
+/// [`Rx::mute`] and left by the hardware, so that a node only receives the frames meant for it.
+pub enum WakeUp {
+    /// Leave mute mode when an idle line is detected
+    IdleLine,
+    /// Leave mute mode when an address mark with the given 4 bit node address is received.
+    ///
+    /// The address mark is the most significant bit of a word (bit 7 with 8 data bits, bit 8 with
+    /// 9 data bits). The node address is stored in the 4 least significant bits of an address
+    /// word. Address words with a different address (re-)enter mute mode.
+    AddressMark(u8),
+}
+
+/// IrDA SIR encoder/decoder mode
+///
+/// IrDA is a half duplex protocol: while the transmitter is busy, data on the receive line is
+/// ignored by the decoder.
+pub enum IrdaMode {
+    /// IrDA disabled, the USART operates as a regular UART
+    Disabled,
+    /// IrDA SIR normal mode: pulses are 3/16 of a bit period wide
+    Normal,
+    /// IrDA SIR low-power mode: pulses are 3 periods of the low-power clock wide (~1.6 us).
+    ///
+    /// The low-power clock is derived from the bus clock so that it is as close as possible to
+    /// 1.8432 MHz.
+    LowPower,
+}
+
 pub struct Config {
     pub baudrate: Bps,
     pub parity: Parity,
     pub stopbits: StopBits,
+    pub wordlength: WordLength,
+    pub wakeup: WakeUp,
+    pub irda: IrdaMode,
 }
 
 impl Config {
@@ -163,6 +218,28 @@ impl Config {
         self.stopbits = stopbits;
         self
     }
+
+    pub fn wordlength_8bits(mut self) -> Self {
+        self.wordlength = WordLength::DataBits8;
+        self
+    }
+
+    pub fn wordlength_9bits(mut self) -> Self {
+        self.wordlength = WordLength::DataBits9;
+        self
+    }
+
+    /// Selects how the receiver wakes up from mute mode
+    pub fn wakeup(mut self, wakeup: WakeUp) -> Self {
+        self.wakeup = wakeup;
+        self
+    }
+
+    /// Routes the data through the IrDA SIR encoder/decoder. IrDA requires 1 stop bit.
+    pub fn irda(mut self, irda: IrdaMode) -> Self {
+        self.irda = irda;
+        self
+    }
 }
 
 impl Default for Config {
@@ -172,10 +249,16 @@ impl Default for Config {
             baudrate,
             parity: Parity::ParityNone,
             stopbits: StopBits::STOP1,
+            wordlength: WordLength::DataBits8,
+            wakeup: WakeUp::IdleLine,
+            irda: IrdaMode::Disabled,
         }
     }
 }
 
+/// Frequency of the IrDA low-power clock as recommended by the IrDA specification
+const IRDA_LOW_POWER_FREQ: u32 = 1_843_200;
+
 /// Serial abstraction
 pub struct Serial<USART, PINS> {
     usart: USART,
@@ -185,11 +268,23 @@ pub struct Serial<USART, PINS> {
 pub trait Instance:
     crate::Sealed + Deref<Target = crate::pac::usart1::RegisterBlock> + Enable + Reset + GetBusFreq
 {
+    #[doc(hidden)]
+    fn ptr() -> *const crate::pac::usart1::RegisterBlock;
+}
+
+macro_rules! inst {
+    ($($USARTX:ident)+) => {
+        $(
+            impl Instance for $USARTX {
+                fn ptr() -> *const crate::pac::usart1::RegisterBlock {
+                    <$USARTX>::ptr()
+                }
+            }
+        )+
+    };
 }
 
-impl Instance for USART1 {}
-impl Instance for USART2 {}
-impl Instance for USART3 {}
+inst! { USART1 USART2 USART3 }
 
 /// Serial receiver
 pub struct Rx<USART> {
@@ -201,9 +296,15 @@ pub struct Tx<USART> {
     _usart: PhantomData<USART>,
 }
 
+/// Word types which can be read from and written to the data register
+trait DataWord: Copy {}
+impl DataWord for u8 {}
+impl DataWord for u16 {}
+
 /// Internal trait for the serial read / write logic.
 trait UsartReadWrite: Deref<Target = crate::pac::usart1::RegisterBlock> {
-    fn read(&self) -> nb::Result<u8, Error> {
+    /// Reads a `u8` or a `u16` word from the data register
+    fn read<Word: DataWord>(&self) -> nb::Result<Word, Error> {
         let sr = self.sr.read();
 
         // Check for any errors
@@ -241,13 +342,14 @@ trait UsartReadWrite: Deref<Target = crate::pac::usart1::RegisterBlock> {
         }
     }
 
-    fn write(&self, byte: u8) -> nb::Result<(), Infallible> {
+    /// Writes a `u8` or a `u16` word to the data register
+    fn write<Word: DataWord>(&self, word: Word) -> nb::Result<(), Infallible> {
         let sr = self.sr.read();
 
         if sr.txe().bit_is_set() {
             // NOTE(unsafe) atomic write to stateless register
             // NOTE(write_volatile) 8-bit write that's not possible through the svd2rust API
-            unsafe { ptr::write_volatile(&self.dr as *const _ as *mut _, byte) }
+            unsafe { ptr::write_volatile(&self.dr as *const _ as *mut _, word) }
             Ok(())
         } else {
             Err(nb::Error::WouldBlock)
@@ -266,6 +368,89 @@ trait UsartReadWrite: Deref<Target = crate::pac::usart1::RegisterBlock> {
 }
 impl UsartReadWrite for &crate::pac::usart1::RegisterBlock {}
 
+/// Applies baud rate, parity, word length, wake up method, stop bits and IrDA mode
+fn apply_config<USART: Instance>(config: Config, clocks: &Clocks) {
+    // NOTE(unsafe) the caller has exclusive access to the configuration registers
+    let usart = unsafe { &*USART::ptr() };
+
+    // Configure baud rate
+    let brr = USART::get_frequency(clocks).0 / config.baudrate.0;
+    assert!(brr >= 16, "impossible baud rate");
+    usart.brr.write(|w| unsafe { w.bits(brr) });
+
+    // Configure parity and word length
+    // Unlike most uart devices, the "word length" of this usart device refers to
+    // the size of the data plus the parity bit. I.e. "word length"=8, parity=even
+    // results in 7 bits of data. Therefore, in order to get 8 bits and one parity
+    // bit, we need to set the "word" length to 9 when using parity bits.
+    let (parity_control_enable, parity) = match config.parity {
+        Parity::ParityNone => (false, false),
+        Parity::ParityEven => (true, false),
+        Parity::ParityOdd => (true, true),
+    };
+    let word_length = match config.wordlength {
+        WordLength::DataBits8 => parity_control_enable,
+        WordLength::DataBits9 => {
+            assert!(!parity_control_enable, "9 data bits do not support parity");
+            true
+        }
+    };
+
+    // Configure the wake up method from mute mode
+    let (wake, address) = match config.wakeup {
+        WakeUp::IdleLine => (false, 0),
+        WakeUp::AddressMark(address) => {
+            assert!(address < 16, "node address must fit in 4 bits");
+            (true, address)
+        }
+    };
+    usart.cr2.modify(|_r, w| w.add().bits(address));
+
+    usart.cr1.modify(|_r, w| {
+        w.m()
+            .bit(word_length)
+            .ps()
+            .bit(parity)
+            .pce()
+            .bit(parity_control_enable)
+            .wake()
+            .bit(wake)
+    });
+
+    // Configure stop bits
+    let stop_bits = match config.stopbits {
+        StopBits::STOP1 => 0b00,
+        StopBits::STOP0P5 => 0b01,
+        StopBits::STOP2 => 0b10,
+        StopBits::STOP1P5 => 0b11,
+    };
+    usart.cr2.modify(|_r, w| w.stop().bits(stop_bits));
+
+    // Configure IrDA SIR mode
+    // The prescaler divides the bus clock down to the low-power clock and must be 1 in
+    // normal mode
+    let (irda_enable, irda_low_power, irda_psc) = match config.irda {
+        IrdaMode::Disabled => (false, false, 0),
+        IrdaMode::Normal => (true, false, 1),
+        IrdaMode::LowPower => {
+            // Rounded so that the low-power clock is as close as possible to the nominal one
+            let psc =
+                (USART::get_frequency(clocks).0 + IRDA_LOW_POWER_FREQ / 2) / IRDA_LOW_POWER_FREQ;
+            (true, true, psc.clamp(1, 255) as u8)
+        }
+    };
+    if irda_enable {
+        assert!(
+            matches!(config.stopbits, StopBits::STOP1),
+            "IrDA requires 1 stop bit"
+        );
+    }
+    usart.gtpr.modify(|_r, w| unsafe { w.psc().bits(irda_psc) });
+    usart
+        .cr3
+        .modify(|_r, w| w.iren().bit(irda_enable).irlp().bit(irda_low_power));
+}
+
 impl<USART, PINS> Serial<USART, PINS>
 where
     USART: Instance,
@@ -277,38 +462,7 @@ where
         USART::reset(rcc);
 
         remap();
-        // Configure baud rate
-        let brr = USART::get_frequency(&clocks).0 / config.baudrate.0;
-        assert!(brr >= 16, "impossible baud rate");
-        self.usart.brr.write(|w| unsafe { w.bits(brr) });
-
-        // Configure parity and word length
-        // Unlike most uart devices, the "word length" of this usart device refers to
-        // the size of the data plus the parity bit. I.e. "word length"=8, parity=even
-        // results in 7 bits of data. Therefore, in order to get 8 bits and one parity
-        // bit, we need to set the "word" length to 9 when using parity bits.
-        let (word_length, parity_control_enable, parity) = match config.parity {
-            Parity::ParityNone => (false, false, false),
-            Parity::ParityEven => (true, true, false),
-            Parity::ParityOdd => (true, true, true),
-        };
-        self.usart.cr1.modify(|_r, w| {
-            w.m()
-                .bit(word_length)
-                .ps()
-                .bit(parity)
-                .pce()
-                .bit(parity_control_enable)
-        });
-
-        // Configure stop bits
-        let stop_bits = match config.stopbits {
-            StopBits::STOP1 => 0b00,
-            StopBits::STOP0P5 => 0b01,
-            StopBits::STOP2 => 0b10,
-            StopBits::STOP1P5 => 0b11,
-        };
-        self.usart.cr2.modify(|_r, w| w.stop().bits(stop_bits));
+        apply_config::<USART>(config, &clocks);
 
         // UE: enable USART
         // RE: enable receiver
@@ -342,6 +496,34 @@ where
         }
     }
 
+    /// Puts the receiver into mute mode. It is woken up by the hardware according to the
+    /// configured [`WakeUp`] method.
+    pub fn mute(&mut self) {
+        self.usart.cr1.modify(|_, w| w.rwu().set_bit());
+    }
+
+    /// Returns `true` if the receiver is in mute mode
+    pub fn is_muted(&self) -> bool {
+        self.usart.cr1.read().rwu().bit_is_set()
+    }
+
+    /// Reads a 9-bit word, see [`WordLength::DataBits9`]
+    pub fn read9(&mut self) -> nb::Result<u16, Error> {
+        self.usart.deref().read()
+    }
+
+    /// Writes a 9-bit word, see [`WordLength::DataBits9`]
+    pub fn write9(&mut self, word: u16) -> nb::Result<(), Infallible> {
+        self.usart.deref().write(word)
+    }
+
+    /// Applies a new configuration to the running port
+    ///
+    /// Returns `WouldBlock` until an ongoing transmission is complete.
+    pub fn reconfigure(&mut self, config: Config, clocks: &Clocks) -> nb::Result<(), Infallible> {
+        reconfigure_usart::<USART>(config, clocks)
+    }
+
     /// Returns ownership of the borrowed register handles
     pub fn release(self) -> (USART, PINS) {
         (self.usart, self.pins)
@@ -361,6 +543,33 @@ where
     }
 }
 
+/// Applies a new configuration to a port which has been split into `Tx` and `Rx`
+///
+/// Returns `WouldBlock` until an ongoing transmission is complete.
+pub fn reconfigure<USART: Instance>(
+    _tx: &mut Tx<USART>,
+    _rx: &mut Rx<USART>,
+    config: Config,
+    clocks: &Clocks,
+) -> nb::Result<(), Infallible> {
+    reconfigure_usart::<USART>(config, clocks)
+}
+
+fn reconfigure_usart<USART: Instance>(
+    config: Config,
+    clocks: &Clocks,
+) -> nb::Result<(), Infallible> {
+    let usart = unsafe { &*USART::ptr() };
+    usart.flush()?;
+
+    // The frame format must not be changed while the USART is enabled
+    usart.cr1.modify(|_, w| w.ue().clear_bit());
+    apply_config::<USART>(config, clocks);
+    usart.cr1.modify(|_, w| w.ue().set_bit());
+
+    Ok(())
+}
+
 macro_rules! hal {
     (
         $(#[$meta:meta])*
@@ -418,6 +627,11 @@ macro_rules! hal {
             pub fn unlisten(&mut self) {
                 unsafe { (*$USARTX::ptr()).cr1.modify(|_, w| w.txeie().clear_bit()) };
             }
+
+            /// Writes a 9-bit word, see [`WordLength::DataBits9`]
+            pub fn write9(&mut self, word: u16) -> nb::Result<(), Infallible> {
+                unsafe { &*$USARTX::ptr() }.write(word)
+            }
         }
 
         impl Rx<$USARTX> {
@@ -428,6 +642,22 @@ macro_rules! hal {
             pub fn unlisten(&mut self) {
                 unsafe { (*$USARTX::ptr()).cr1.modify(|_, w| w.rxneie().clear_bit()) };
             }
+
+            /// Puts the receiver into mute mode. It is woken up by the hardware according to
+            /// the configured [`WakeUp`] method.
+            pub fn mute(&mut self) {
+                unsafe { (*$USARTX::ptr()).cr1.modify(|_, w| w.rwu().set_bit()) };
+            }
+
+            /// Returns `true` if the receiver is in mute mode
+            pub fn is_muted(&self) -> bool {
+                unsafe { (*$USARTX::ptr()).cr1.read().rwu().bit_is_set() }
+            }
+
+            /// Reads a 9-bit word, see [`WordLength::DataBits9`]
+            pub fn read9(&mut self) -> nb::Result<u16, Error> {
+                unsafe { &*$USARTX::ptr() }.read()
+            }
         }
 
         impl crate::hal::serial::Read<u8> for Rx<$USARTX> {
@@ -448,6 +678,25 @@ macro_rules! hal {
                 unsafe { &*$USARTX::ptr() }.write(byte)
             }
         }
+
+        impl crate::hal::serial::Read<u16> for Rx<$USARTX> {
+            type Error = Error;
+
+            fn read(&mut self) -> nb::Result<u16, Error> {
+                unsafe { &*$USARTX::ptr() }.read()
+            }
+        }
+
+        impl crate::hal::serial::Write<u16> for Tx<$USARTX> {
+            type Error = Infallible;
+
+            fn flush(&mut self) -> nb::Result<(), Self::Error> {
+                unsafe { &*$USARTX::ptr() }.flush()
+            }
+            fn write(&mut self, word: u16) -> nb::Result<(), Self::Error> {
+                unsafe { &*$USARTX::ptr() }.write(word)
+            }
+        }
     };
 }
 
@@ -477,6 +726,32 @@ where
     }
 }
 
+impl<USART, PINS> crate::hal::serial::Read<u16> for Serial<USART, PINS>
+where
+    USART: Instance,
+{
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u16, Error> {
+        self.usart.deref().read()
+    }
+}
+
+impl<USART, PINS> crate::hal::serial::Write<u16> for Serial<USART, PINS>
+where
+    USART: Instance,
+{
+    type Error = Infallible;
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.usart.deref().flush()
+    }
+
+    fn write(&mut self, word: u16) -> nb::Result<(), Self::Error> {
+        self.usart.deref().write(word)
+    }
+}
+
 impl<USART> core::fmt::Write for Tx<USART>
 where
     Tx<USART>: embedded_hal::serial::Write<u8>,
@@ -569,7 +844,7 @@ macro_rules! serialdma {
 
             impl Rx<$USARTX> {
                 pub fn with_dma(self, channel: $dmarxch) -> $rxdma {
-                    unsafe { (*$USARTX::ptr()).cr3.write(|w| w.dmar().set_bit()); }
+                    unsafe { (*$USARTX::ptr()).cr3.modify(|_, w| w.dmar().set_bit()); }
                     RxDma {
                         payload: self,
                         channel,
@@ -579,7 +854,7 @@ macro_rules! serialdma {
 
             impl Tx<$USARTX> {
                 pub fn with_dma(self, channel: $dmatxch) -> $txdma {
-                    unsafe { (*$USARTX::ptr()).cr3.write(|w| w.dmat().set_bit()); }
+                    unsafe { (*$USARTX::ptr()).cr3.modify(|_, w| w.dmat().set_bit()); }
                     TxDma {
                         payload: self,
                         channel,
@@ -594,7 +869,7 @@ macro_rules! serialdma {
                 }
                 pub fn release(mut self) -> (Rx<$USARTX>, $dmarxch) {
                     self.stop();
-                    unsafe { (*$USARTX::ptr()).cr3.write(|w| w.dmar().clear_bit()); }
+                    unsafe { (*$USARTX::ptr()).cr3.modify(|_, w| w.dmar().clear_bit()); }
                     let RxDma {payload, channel} = self;
                     (
                         payload,
@@ -610,7 +885,7 @@ macro_rules! serialdma {
                 }
                 pub fn release(mut self) -> (Tx<$USARTX>, $dmatxch) {
                     self.stop();
-                    unsafe { (*$USARTX::ptr()).cr3.write(|w| w.dmat().clear_bit()); }
+                    unsafe { (*$USARTX::ptr()).cr3.modify(|_, w| w.dmat().clear_bit()); }
                     let TxDma {payload, channel} = self;
                     (
                         payload,
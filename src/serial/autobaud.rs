@@ -0,0 +1,263 @@
+//! # Automatic baud rate detection
+//!
+//! The baud rate of the remote end is measured on the RX pin with a timer input capture channel
+//! while the remote end sends the sync byte `0x55` (`'U'`). Starting with the start bit, every
+//! second bit of this byte is low, so its five falling edges are two bit times apart. The measured
+//! baud rate is then written to the baud rate register.
+//!
+//! The RX pin has to be connected to one of the following timer channels:
+//!
+//! | USART  | RX   | Timer channel | Timer remap |
+//! |:------:|:----:|:-------------:|:-----------:|
+//! | USART1 | PA10 | TIM1 CH3      | 0           |
+//! | USART1 | PB7  | TIM4 CH2      | 0           |
+//! | USART2 | PA3  | TIM2 CH4      | 0           |
+//! | USART3 | PB11 | TIM2 CH4      | 0b10        |
+//!
+//! The timer remap is set up by [`Serial::autobaud`] and restored before it returns. The receiver
+//! is disabled while measuring.
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! let mut timer = Timer::tim1(dp.TIM1, &clocks);
+//! // Blocks until the remote end has sent 0x55, or for at most 1 second
+//! let baudrate = serial
+//!     .autobaud(&mut timer, &mut afio.mapr, 1200.bps(), 1000.ms(), &clocks)
+//!     .unwrap();
+//!  ```
+
+use crate::afio::MAPR;
+use crate::gpio::gpioa::PA3;
+use crate::gpio::gpiob::PB11;
+use crate::gpio::{Floating, Input};
+use crate::pac::{TIM2, USART2, USART3};
+use crate::rcc::{Clocks, GetBusFreq};
+use crate::time::{Bps, MilliSeconds, U32Ext};
+use crate::timer::Timer;
+
+#[cfg(any(
+    feature = "stm32f100",
+    feature = "stm32f103",
+    feature = "connectivity",
+    feature = "medium"
+))]
+use crate::pac::USART1;
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+use crate::{gpio::gpioa::PA10, pac::TIM1};
+#[cfg(feature = "medium")]
+use crate::{gpio::gpiob::PB7, pac::TIM4};
+
+use super::{Error, Serial, UsartReadWrite};
+
+/// Number of bit times between the first and the last falling edge of the sync byte
+const SYNC_BITS: u32 = 8;
+
+/// Timer prescaler so that two bit times at `min_baudrate` fit into the 16 bit counter
+fn prescaler(timer_clk: u32, min_baudrate: Bps) -> u16 {
+    let ticks = timer_clk / min_baudrate.0 * 2;
+    (ticks / 0x1_0000) as u16
+}
+
+/// Number of counter overflows after which `timeout` has elapsed, at least 1
+fn overflows(timer_clk: u32, psc: u16, timeout: MilliSeconds) -> u32 {
+    let ticks_per_ms = timer_clk / 1000 / (psc as u32 + 1);
+    core::cmp::max(1, timeout.0.saturating_mul(ticks_per_ms) / 0x1_0000)
+}
+
+/// Measures the falling edges of the sync byte, returns the duration of `SYNC_BITS` bit times
+///
+/// `capture` blocks until the next falling edge and returns the captured counter value.
+fn measure(mut capture: impl FnMut() -> Result<u16, Error>) -> Result<u32, Error> {
+    let mut edges = [0; 5];
+    for edge in edges.iter_mut() {
+        *edge = capture()?;
+    }
+
+    let total: u32 = edges
+        .windows(2)
+        .map(|w| w[1].wrapping_sub(w[0]) as u32)
+        .sum();
+    let expected = total / 4;
+
+    // All edges of the sync byte are two bit times apart, allow an error of 25 %
+    let in_range = edges.windows(2).all(|w| {
+        let interval = w[1].wrapping_sub(w[0]) as u32;
+        interval >= expected - expected / 4 && interval <= expected + expected / 4
+    });
+    if in_range && total != 0 {
+        Ok(total)
+    } else {
+        Err(Error::Framing)
+    }
+}
+
+macro_rules! autobaud {
+    ($(
+        $(#[$meta:meta])*
+        $USARTX:ident: (
+            $RX:ident,
+            $TIMX:ident,
+            $timX_remap:ident: $bit:ident($remap:expr),
+            $ccmrX_input:ident,
+            $ccXs:ident: $tiX:ident,
+            $ccXe:ident,
+            $ccXp:ident,
+            $ccXif:ident,
+            $ccXof:ident,
+            $ccrX:ident,
+        ),
+    )+) => {
+        $(
+            $(#[$meta])*
+            impl<TX> Serial<$USARTX, (TX, $RX<Input<Floating>>)> {
+                /// Measures the baud rate of the sync byte `0x55` and applies it
+                ///
+                /// Blocks until the sync byte has been received, or returns `Error::Timeout`
+                /// once `timeout` has elapsed. `min_baudrate` is the lowest baud rate which can
+                /// be measured. Returns the detected baud rate, or `Error::Framing` if the
+                /// received byte does not look like the sync byte.
+                pub fn autobaud(
+                    &mut self,
+                    timer: &mut Timer<$TIMX>,
+                    mapr: &mut MAPR,
+                    min_baudrate: Bps,
+                    timeout: MilliSeconds,
+                    clocks: &Clocks,
+                ) -> Result<Bps, Error> {
+                    let mut previous = Default::default();
+                    #[allow(unused_unsafe)]
+                    mapr.modify_mapr(|r, w| unsafe {
+                        previous = r.$timX_remap().$bit();
+                        w.$timX_remap().$bit($remap)
+                    });
+
+                    let tim = &timer.tim;
+                    let psc = prescaler(timer.clk.0, min_baudrate);
+                    tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    tim.psc.write(|w| w.psc().bits(psc));
+                    tim.arr.write(|w| w.arr().bits(u16::MAX));
+                    tim.egr.write(|w| w.ug().set_bit());
+
+                    // Capture falling edges of the RX pin, without filter
+                    tim.ccer.modify(|_, w| w.$ccXe().clear_bit());
+                    tim.$ccmrX_input().modify(|_, w| w.$ccXs().$tiX());
+                    tim.ccer.modify(|_, w| w.$ccXp().set_bit().$ccXe().set_bit());
+                    tim.sr.modify(|_, w| {
+                        w.uif().clear_bit().$ccXif().clear_bit().$ccXof().clear_bit()
+                    });
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                    // Keep the receiver from sampling at the wrong baud rate
+                    self.usart.cr1.modify(|_, w| w.re().clear_bit());
+
+                    // The counter overflows at least every two bit times at `min_baudrate`
+                    let mut remaining = overflows(timer.clk.0, psc, timeout);
+                    let mut capture = || loop {
+                        let sr = tim.sr.read();
+                        if sr.$ccXof().bit_is_set() {
+                            return Err(Error::Overrun);
+                        }
+                        if sr.$ccXif().bit_is_set() {
+                            // Reading the capture register clears the flag
+                            return Ok(tim.$ccrX.read().ccr().bits());
+                        }
+                        if sr.uif().bit_is_set() {
+                            // Only clear the update flag, the other flags are unaffected by 1s
+                            tim.sr.write(|w| unsafe { w.bits(0xffff & !1) });
+                            remaining -= 1;
+                            if remaining == 0 {
+                                return Err(Error::Timeout);
+                            }
+                        }
+                    };
+                    let result = measure(&mut capture);
+
+                    if result.is_ok() {
+                        // Wait for the rising edge of the stop bit
+                        tim.ccer.modify(|_, w| w.$ccXp().clear_bit());
+                        capture().ok();
+                    }
+
+                    tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    tim.ccer.modify(|_, w| w.$ccXe().clear_bit());
+                    #[allow(unused_unsafe)]
+                    mapr.modify_mapr(|_, w| unsafe { w.$timX_remap().$bit(previous) });
+
+                    let baudrate = result.and_then(|ticks| {
+                        let baudrate = timer.clk.0 / (psc as u32 + 1) * SYNC_BITS / ticks;
+                        let brr = $USARTX::get_frequency(clocks).0 / baudrate;
+                        if brr >= 16 {
+                            self.usart.brr.write(|w| unsafe { w.bits(brr) });
+                            Ok(baudrate.bps())
+                        } else {
+                            Err(Error::Framing)
+                        }
+                    });
+
+                    self.usart.cr1.modify(|_, w| w.re().set_bit());
+                    // Drop anything the receiver has picked up before it was disabled
+                    (&*self.usart).read::<u8>().ok();
+
+                    baudrate
+                }
+            }
+        )+
+    }
+}
+
+autobaud! {
+    #[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+    USART1: (
+        PA10,
+        TIM1,
+        tim1_remap: bits(0),
+        ccmr2_input,
+        cc3s: ti3,
+        cc3e,
+        cc3p,
+        cc3if,
+        cc3of,
+        ccr3,
+    ),
+    USART2: (
+        PA3,
+        TIM2,
+        tim2_remap: bits(0),
+        ccmr2_input,
+        cc4s: ti4,
+        cc4e,
+        cc4p,
+        cc4if,
+        cc4of,
+        ccr4,
+    ),
+    USART3: (
+        PB11,
+        TIM2,
+        tim2_remap: bits(0b10),
+        ccmr2_input,
+        cc4s: ti4,
+        cc4e,
+        cc4p,
+        cc4if,
+        cc4of,
+        ccr4,
+    ),
+}
+
+#[cfg(feature = "medium")]
+autobaud! {
+    USART1: (
+        PB7,
+        TIM4,
+        tim4_remap: bit(false),
+        ccmr1_input,
+        cc2s: ti2,
+        cc2e,
+        cc2p,
+        cc2if,
+        cc2of,
+        ccr2,
+    ),
+}
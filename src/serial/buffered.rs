@@ -0,0 +1,243 @@
+//! # Interrupt driven buffered serial
+//!
+//! [`BufferedSerial`] moves data between the USART and two ring buffers from the
+//! _Received data ready to be read (RXNE)_ and _Transmit data register empty (TXE)_ interrupts.
+//! The application only accesses the ring buffers, so it never has to wait for the USART.
+//!
+//! [`BufferedSerial::on_interrupt`] must be called from the USART interrupt handler. As both the
+//! interrupt handler and the application access the `BufferedSerial`, it is usually shared
+//! through a `cortex_m::interrupt::Mutex`.
+//!
+//! Receive errors do not interrupt the data flow, they are counted in [`ErrorCounters`] instead.
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! static mut TX_BUFFER: [u8; 64] = [0; 64];
+//! static mut RX_BUFFER: [u8; 64] = [0; 64];
+//!
+//! let (tx, rx) = serial.split();
+//! let mut serial = BufferedSerial::new(tx, rx, unsafe { &mut TX_BUFFER }, unsafe { &mut RX_BUFFER });
+//!
+//! serial.write_all(b"hello").ok();
+//!
+//! let mut buffer = [0; 16];
+//! let len = serial.read(&mut buffer);
+//!
+//! // ... in the USART interrupt
+//! serial.on_interrupt();
+//!  ```
+
+use core::convert::Infallible;
+
+use super::{Error, Instance, Rx, Tx, UsartReadWrite};
+
+/// Number of receive errors since the last call of [`BufferedSerial::clear_errors`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ErrorCounters {
+    /// Bytes lost because the USART was not read in time
+    pub overrun: u32,
+    /// Bytes received with a framing error
+    pub framing: u32,
+    /// Bytes received with noise
+    pub noise: u32,
+    /// Bytes received with a parity error
+    pub parity: u32,
+    /// Bytes dropped because the receive buffer was full
+    pub buffer_full: u32,
+}
+
+struct RingBuffer {
+    buffer: &'static mut [u8],
+    /// Index of the oldest byte
+    read: usize,
+    /// Number of bytes in the buffer
+    len: usize,
+}
+
+impl RingBuffer {
+    fn new(buffer: &'static mut [u8]) -> Self {
+        RingBuffer {
+            buffer,
+            read: 0,
+            len: 0,
+        }
+    }
+
+    fn free(&self) -> usize {
+        self.buffer.len() - self.len
+    }
+
+    fn push(&mut self, byte: u8) -> bool {
+        if self.free() == 0 {
+            return false;
+        }
+        let write = (self.read + self.len) % self.buffer.len();
+        self.buffer[write] = byte;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.buffer[self.read];
+        self.read = (self.read + 1) % self.buffer.len();
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+/// Serial port with interrupt driven transmit and receive buffers
+pub struct BufferedSerial<USART> {
+    tx: Tx<USART>,
+    rx: Rx<USART>,
+    tx_buffer: RingBuffer,
+    rx_buffer: RingBuffer,
+    errors: ErrorCounters,
+}
+
+impl<USART> BufferedSerial<USART>
+where
+    USART: Instance,
+{
+    /// Creates the buffered serial port and starts listening to the RXNE interrupt
+    pub fn new(
+        tx: Tx<USART>,
+        rx: Rx<USART>,
+        tx_buffer: &'static mut [u8],
+        rx_buffer: &'static mut [u8],
+    ) -> Self {
+        assert!(!tx_buffer.is_empty() && !rx_buffer.is_empty());
+        unsafe { (*USART::ptr()).cr1.modify(|_, w| w.rxneie().set_bit()) };
+        BufferedSerial {
+            tx,
+            rx,
+            tx_buffer: RingBuffer::new(tx_buffer),
+            rx_buffer: RingBuffer::new(rx_buffer),
+            errors: ErrorCounters::default(),
+        }
+    }
+
+    /// Handles the USART interrupt
+    ///
+    /// Moves a received byte into the receive buffer and the next byte of the transmit buffer
+    /// into the USART.
+    pub fn on_interrupt(&mut self) {
+        let usart = unsafe { &*USART::ptr() };
+
+        match usart.read::<u8>() {
+            Ok(byte) => {
+                if !self.rx_buffer.push(byte) {
+                    self.errors.buffer_full += 1;
+                }
+            }
+            Err(nb::Error::Other(error)) => match error {
+                Error::Overrun => self.errors.overrun += 1,
+                Error::Framing => self.errors.framing += 1,
+                Error::Noise => self.errors.noise += 1,
+                Error::Parity => self.errors.parity += 1,
+                // Only returned by the baud rate detection
+                Error::Timeout => {}
+            },
+            Err(nb::Error::WouldBlock) => {}
+        }
+
+        if usart.cr1.read().txeie().bit_is_set() && usart.sr.read().txe().bit_is_set() {
+            if let Some(byte) = self.tx_buffer.pop() {
+                usart.write(byte).ok();
+            } else {
+                // Nothing left to send
+                usart.cr1.modify(|_, w| w.txeie().clear_bit());
+            }
+        }
+    }
+
+    /// Queues all bytes of `data` for transmission
+    ///
+    /// Returns `WouldBlock` without queueing anything if the transmit buffer does not have room
+    /// for all of `data`.
+    pub fn write_all(&mut self, data: &[u8]) -> nb::Result<(), Infallible> {
+        if self.tx_buffer.free() < data.len() {
+            return Err(nb::Error::WouldBlock);
+        }
+        for byte in data {
+            self.tx_buffer.push(*byte);
+        }
+        self.start_transmission();
+        Ok(())
+    }
+
+    /// Copies received bytes into `buffer`, returns the number of copied bytes
+    pub fn read(&mut self, buffer: &mut [u8]) -> usize {
+        let mut count = 0;
+        for slot in buffer.iter_mut() {
+            match self.rx_buffer.pop() {
+                Some(byte) => *slot = byte,
+                None => break,
+            }
+            count += 1;
+        }
+        count
+    }
+
+    /// Number of received bytes waiting in the receive buffer
+    pub fn available(&self) -> usize {
+        self.rx_buffer.len
+    }
+
+    /// Returns the receive error counters
+    pub fn errors(&self) -> ErrorCounters {
+        self.errors
+    }
+
+    /// Resets the receive error counters
+    pub fn clear_errors(&mut self) {
+        self.errors = ErrorCounters::default();
+    }
+
+    /// Stops listening to the interrupts and returns the transmitter, the receiver and the buffers
+    ///
+    /// Data which has not been sent or read yet is discarded.
+    pub fn release(self) -> (Tx<USART>, Rx<USART>, &'static mut [u8], &'static mut [u8]) {
+        unsafe {
+            (*USART::ptr())
+                .cr1
+                .modify(|_, w| w.rxneie().clear_bit().txeie().clear_bit())
+        };
+        (
+            self.tx,
+            self.rx,
+            self.tx_buffer.buffer,
+            self.rx_buffer.buffer,
+        )
+    }
+
+    /// Enables the TXE interrupt, which sends the queued bytes
+    fn start_transmission(&mut self) {
+        if self.tx_buffer.len != 0 {
+            unsafe { (*USART::ptr()).cr1.modify(|_, w| w.txeie().set_bit()) };
+        }
+    }
+}
+
+impl<USART> crate::hal::serial::Write<u8> for BufferedSerial<USART>
+where
+    USART: Instance,
+{
+    type Error = Infallible;
+
+    /// Queues a single byte for transmission
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.write_all(&[byte])
+    }
+
+    /// Waits until all queued bytes have been sent
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        if self.tx_buffer.len != 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+        unsafe { &*USART::ptr() }.flush()
+    }
+}
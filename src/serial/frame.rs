@@ -0,0 +1,245 @@
+//! # Variable-length frame reception
+//!
+//! Many serial protocols (NMEA, Modbus RTU, AT commands) send frames of unknown length which are
+//! separated by a pause on the line. [`FrameReader`] receives into a ring buffer using circular
+//! DMA and uses the _Idle line detected (IDLE)_ interrupt of the USART to find the end of a frame.
+//!
+//! The DMA half transfer and transfer complete interrupts are used to keep track of the amount of
+//! received data. [`FrameReader::poll`] must therefore be called from both the USART and the DMA
+//! channel interrupt handler. A frame may wrap around the end of the ring buffer, so it is handed
+//! out as a [`Frame`] made of up to two slices.
+//!
+//! A frame is only valid until the DMA has received another buffer length of data. Copy it with
+//! [`Frame::copy_to`] if it is needed for longer. If the DMA overwrites data which has not been
+//! handed out yet, or the USART reports an overrun, the partial frame is dropped and
+//! [`Error::Overrun`] is returned.
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! static mut BUFFER: [u8; 128] = [0; 128];
+//!
+//! let rx = serial.split().1.with_dma(dma1.5);
+//! let mut reader = rx.frame_reader(unsafe { &mut BUFFER });
+//!
+//! // ... in the USART1 and DMA1_CHANNEL5 interrupts
+//! match reader.poll() {
+//!     Ok(Some(frame)) => {
+//!         let mut line = [0; 82];
+//!         let len = frame.copy_to(&mut line);
+//!     }
+//!     Ok(None) => {}
+//!     Err(_) => {}
+//! }
+//!  ```
+
+use core::sync::atomic::{self, Ordering};
+
+use crate::dma::{Event, TransferPayload};
+use crate::pac::{USART1, USART2, USART3};
+
+use super::{Error, RxDma1, RxDma2, RxDma3};
+
+/// Frame receiver using circular DMA and idle line detection
+pub struct FrameReader<RXDMA, const N: usize> {
+    rx: RXDMA,
+    buffer: &'static mut [u8; N],
+    /// Position of the first byte of the current frame
+    frame_start: usize,
+    /// Number of bytes received for the current frame
+    frame_len: usize,
+    /// Position of the DMA when the reader was last polled
+    write_index: usize,
+}
+
+/// A received frame, borrowed from the ring buffer of a [`FrameReader`]
+pub struct Frame<'a> {
+    first: &'a [u8],
+    second: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    fn new(buffer: &'a [u8], start: usize, len: usize) -> Self {
+        if start + len <= buffer.len() {
+            Frame {
+                first: &buffer[start..start + len],
+                second: &[],
+            }
+        } else {
+            Frame {
+                first: &buffer[start..],
+                second: &buffer[..start + len - buffer.len()],
+            }
+        }
+    }
+
+    /// Number of bytes in the frame
+    pub fn len(&self) -> usize {
+        self.first.len() + self.second.len()
+    }
+
+    /// Returns `true` if the frame contains no data
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the frame as two slices. The second one is only non-empty if the frame wraps
+    /// around the end of the ring buffer.
+    pub fn as_slices(&self) -> (&'a [u8], &'a [u8]) {
+        (self.first, self.second)
+    }
+
+    /// Iterates over the bytes of the frame
+    pub fn iter(&self) -> impl Iterator<Item = &'a u8> {
+        self.first.iter().chain(self.second.iter())
+    }
+
+    /// Copies the frame into `buffer` and returns the number of copied bytes.
+    ///
+    /// If `buffer` is shorter than the frame, the end of the frame is cut off.
+    pub fn copy_to(&self, buffer: &mut [u8]) -> usize {
+        let first = self.first.len().min(buffer.len());
+        buffer[..first].copy_from_slice(&self.first[..first]);
+        let second = self.second.len().min(buffer.len() - first);
+        buffer[first..first + second].copy_from_slice(&self.second[..second]);
+        first + second
+    }
+}
+
+impl<RXDMA, const N: usize> FrameReader<RXDMA, N> {
+    /// Accounts for the data received since the last call, returns `true` if a frame is complete
+    fn update(&mut self, position: usize, idle: bool, overrun: bool) -> Result<bool, Error> {
+        let received = (position + N - self.write_index) % N;
+        self.write_index = position;
+        self.frame_len += received;
+
+        if overrun || self.frame_len > N {
+            // Drop the damaged frame and start over at the current position
+            self.frame_start = position;
+            self.frame_len = 0;
+            return Err(Error::Overrun);
+        }
+
+        Ok(idle && self.frame_len > 0)
+    }
+
+    /// Hands out the current frame and starts a new one
+    fn take_frame(&mut self) -> Frame<'_> {
+        let (start, len) = (self.frame_start, self.frame_len);
+        self.frame_start = self.write_index;
+        self.frame_len = 0;
+
+        atomic::compiler_fence(Ordering::Acquire);
+
+        Frame::new(&self.buffer[..], start, len)
+    }
+}
+
+macro_rules! frame_reader {
+    ($(
+        $USARTX:ident: (
+            $rxdma:ident,
+            $chtifX:ident,
+            $ctcifX:ident,
+        ),
+    )+) => {
+        $(
+            impl $rxdma {
+                /// Starts circular DMA reception into `buffer` and listens to the IDLE interrupt
+                pub fn frame_reader<const N: usize>(
+                    mut self,
+                    buffer: &'static mut [u8; N],
+                ) -> FrameReader<$rxdma, N> {
+                    // We own the buffer now and we won't call other `&mut` on it until the
+                    // reception is stopped.
+                    let (ptr, len) = (buffer.as_mut_ptr(), N);
+                    self.channel.set_peripheral_address(unsafe{ &(*$USARTX::ptr()).dr as *const _ as u32 }, false);
+                    self.channel.set_memory_address(ptr as u32, true);
+                    self.channel.set_transfer_length(len);
+
+                    atomic::compiler_fence(Ordering::Release);
+
+                    self.channel.ch().cr.modify(|_, w| { w
+                        .mem2mem() .clear_bit()
+                        .pl()      .medium()
+                        .msize()   .bits8()
+                        .psize()   .bits8()
+                        .circ()    .set_bit()
+                        .dir()     .clear_bit()
+                    });
+                    self.channel.listen(Event::HalfTransfer);
+                    self.channel.listen(Event::TransferComplete);
+
+                    unsafe { (*$USARTX::ptr()).cr1.modify(|_, w| w.idleie().set_bit()) };
+
+                    self.start();
+
+                    FrameReader {
+                        rx: self,
+                        buffer,
+                        frame_start: 0,
+                        frame_len: 0,
+                        write_index: 0,
+                    }
+                }
+            }
+
+            impl<const N: usize> FrameReader<$rxdma, N> {
+                /// Checks for a complete frame
+                ///
+                /// Call this from the USART interrupt handler and from the DMA channel interrupt
+                /// handler. Returns a frame once the line has become idle after receiving data.
+                pub fn poll(&mut self) -> Result<Option<Frame<'_>>, Error> {
+                    let usart = unsafe { &*$USARTX::ptr() };
+                    let sr = usart.sr.read();
+                    let idle = sr.idle().bit_is_set();
+                    let overrun = sr.ore().bit_is_set();
+                    if idle || overrun {
+                        // IDLE and ORE are cleared by reading sr followed by dr
+                        let _ = usart.dr.read();
+                    }
+
+                    // The flags only trigger the interrupt, the position is taken from the DMA
+                    self.rx
+                        .channel
+                        .ifcr()
+                        .write(|w| w.$chtifX().set_bit().$ctcifX().set_bit());
+                    let position = (N - self.rx.channel.get_ndtr() as usize) % N;
+
+                    if self.update(position, idle, overrun)? {
+                        Ok(Some(self.take_frame()))
+                    } else {
+                        Ok(None)
+                    }
+                }
+
+                /// Stops the reception and returns the buffer and the `RxDma`
+                pub fn release(mut self) -> (&'static mut [u8; N], $rxdma) {
+                    self.rx.stop();
+                    self.rx.channel.unlisten(Event::HalfTransfer);
+                    self.rx.channel.unlisten(Event::TransferComplete);
+                    unsafe { (*$USARTX::ptr()).cr1.modify(|_, w| w.idleie().clear_bit()) };
+                    (self.buffer, self.rx)
+                }
+            }
+        )+
+    }
+}
+
+frame_reader! {
+    USART1: (
+        RxDma1,
+        chtif5,
+        ctcif5,
+    ),
+    USART2: (
+        RxDma2,
+        chtif6,
+        ctcif6,
+    ),
+    USART3: (
+        RxDma3,
+        chtif3,
+        ctcif3,
+    ),
+}
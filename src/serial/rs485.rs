@@ -0,0 +1,263 @@
+//! # RS-485 driver enable handling
+//!
+//! RS-485 transceivers need their driver enable (DE, often tied to /RE) pin asserted while
+//! transmitting. The pin has to be released exactly after the last stop bit has left the shift
+//! register, which is signalled by the _Transmission complete (TC)_ flag of the USART.
+//!
+//! [`Rs485`] owns a [`Tx`] and the DE output pin. It asserts DE before data is written and
+//! releases it once the transmission is complete:
+//!
+//! - The blocking `bwrite_all`/`bflush` methods wait for TC and release the bus before returning.
+//! - The non-blocking `write` asserts DE, `flush` releases it once TC is set.
+//! - After a non-blocking or DMA write, [`Rs485::release_on_complete`] enables the TC interrupt.
+//!   Calling [`Rs485::on_interrupt`] from the USART interrupt handler then releases the bus
+//!   without busy waiting.
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! let (tx, rx) = serial.split();
+//! let de = gpioa.pa8.into_push_pull_output(&mut gpioa.crh);
+//! let rs485 = Rs485::new(tx, de).with_dma(dma1.4);
+//!
+//! let transfer = rs485.write(b"request");
+//! // ... in the DMA transfer complete interrupt
+//! let (buffer, mut rs485) = transfer.wait();
+//! rs485.release_on_complete();
+//! // ... in the USART interrupt
+//! rs485.on_interrupt();
+//!  ```
+
+use core::convert::Infallible;
+use core::sync::atomic::{self, Ordering};
+
+use embedded_dma::StaticReadBuffer;
+
+use crate::dma::{dma1, Transfer, TransferPayload, Transmit, TxDma, R};
+use crate::hal::digital::v2::OutputPin;
+use crate::hal::serial::Write;
+use crate::pac::{USART1, USART2, USART3};
+
+use super::Tx;
+
+/// Serial transmitter that drives the DE pin of an RS-485 transceiver
+pub struct Rs485<USART, DE> {
+    tx: Tx<USART>,
+    de: DE,
+}
+
+impl<USART, DE> Rs485<USART, DE>
+where
+    DE: OutputPin,
+{
+    /// Creates the RS-485 transmitter. The DE pin is driven low, releasing the bus.
+    pub fn new(tx: Tx<USART>, mut de: DE) -> Self {
+        de.set_low().ok();
+        Rs485 { tx, de }
+    }
+
+    /// Returns ownership of the transmitter and the DE pin
+    pub fn release(self) -> (Tx<USART>, DE) {
+        (self.tx, self.de)
+    }
+}
+
+macro_rules! rs485 {
+    ($(
+        $USARTX:ident: (
+            $rs485dma:ident,
+            $dmatxch:ty,
+        ),
+    )+) => {
+        $(
+            pub type $rs485dma<DE> = TxDma<Rs485<$USARTX, DE>, $dmatxch>;
+
+            impl<DE> Rs485<$USARTX, DE>
+            where
+                DE: OutputPin,
+            {
+                /// Asserts DE and clears the transmission complete flag
+                fn drive(&mut self) {
+                    self.de.set_high().ok();
+                    let usart = unsafe { &*$USARTX::ptr() };
+                    // TC is cleared by writing 0, the other rc_w0 flags are left untouched
+                    usart.sr.write(|w| {
+                        w.tc()
+                            .clear_bit()
+                            .rxne()
+                            .set_bit()
+                            .lbd()
+                            .set_bit()
+                            .cts()
+                            .set_bit()
+                    });
+                }
+
+                /// Releases the bus from the USART interrupt once the last stop bit has been sent
+                ///
+                /// [`on_interrupt`](Self::on_interrupt) has to be called from the interrupt
+                /// handler of the USART.
+                pub fn release_on_complete(&mut self) {
+                    unsafe { (*$USARTX::ptr()).cr1.modify(|_, w| w.tcie().set_bit()) };
+                }
+
+                /// Handles the _Transmission complete_ interrupt
+                ///
+                /// Releases the bus and stops listening for the interrupt if the transmission is
+                /// complete. Returns `true` if the bus has been released.
+                pub fn on_interrupt(&mut self) -> bool {
+                    let usart = unsafe { &*$USARTX::ptr() };
+                    if usart.cr1.read().tcie().bit_is_set() && usart.sr.read().tc().bit_is_set() {
+                        usart.cr1.modify(|_, w| w.tcie().clear_bit());
+                        self.de.set_low().ok();
+                        true
+                    } else {
+                        false
+                    }
+                }
+
+                /// Enables DMA transmission
+                pub fn with_dma(self, channel: $dmatxch) -> $rs485dma<DE> {
+                    unsafe { (*$USARTX::ptr()).cr3.modify(|_, w| w.dmat().set_bit()); }
+                    TxDma {
+                        payload: self,
+                        channel,
+                    }
+                }
+            }
+
+            impl<DE> Write<u8> for Rs485<$USARTX, DE>
+            where
+                DE: OutputPin,
+            {
+                type Error = Infallible;
+
+                /// Asserts DE and writes a single byte
+                fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+                    if unsafe { (*$USARTX::ptr()).sr.read().txe().bit_is_clear() } {
+                        return Err(nb::Error::WouldBlock);
+                    }
+                    self.drive();
+                    Write::<u8>::write(&mut self.tx, byte)
+                }
+
+                /// Releases the bus once the transmission is complete
+                fn flush(&mut self) -> nb::Result<(), Self::Error> {
+                    Write::<u8>::flush(&mut self.tx)?;
+                    self.de.set_low().ok();
+                    Ok(())
+                }
+            }
+
+            impl<DE> crate::hal::blocking::serial::Write<u8> for Rs485<$USARTX, DE>
+            where
+                DE: OutputPin,
+            {
+                type Error = Infallible;
+
+                /// Writes all bytes and releases the bus after the last stop bit
+                fn bwrite_all(&mut self, buffer: &[u8]) -> Result<(), Self::Error> {
+                    for byte in buffer {
+                        nb::block!(Write::write(self, *byte))?;
+                    }
+                    nb::block!(Write::flush(self))
+                }
+
+                fn bflush(&mut self) -> Result<(), Self::Error> {
+                    nb::block!(Write::flush(self))
+                }
+            }
+
+            impl<DE> Transmit for $rs485dma<DE> {
+                type TxChannel = $dmatxch;
+                type ReceivedWord = u8;
+            }
+
+            impl<DE> TransferPayload for $rs485dma<DE>
+            where
+                DE: OutputPin,
+            {
+                fn start(&mut self) {
+                    self.payload.drive();
+                    self.channel.start();
+                }
+                fn stop(&mut self) {
+                    self.channel.stop();
+                }
+            }
+
+            impl<DE> $rs485dma<DE>
+            where
+                DE: OutputPin,
+            {
+                /// Releases the bus from the USART interrupt once the last stop bit has been sent
+                pub fn release_on_complete(&mut self) {
+                    self.payload.release_on_complete()
+                }
+
+                /// Handles the _Transmission complete_ interrupt
+                ///
+                /// Returns `true` if the bus has been released.
+                pub fn on_interrupt(&mut self) -> bool {
+                    self.payload.on_interrupt()
+                }
+
+                pub fn release(mut self) -> (Rs485<$USARTX, DE>, $dmatxch) {
+                    self.stop();
+                    unsafe { (*$USARTX::ptr()).cr3.modify(|_, w| w.dmat().clear_bit()); }
+                    let TxDma {payload, channel} = self;
+                    (
+                        payload,
+                        channel,
+                    )
+                }
+            }
+
+            impl<B, DE> crate::dma::WriteDma<B, u8> for $rs485dma<DE>
+            where
+                B: StaticReadBuffer<Word = u8>,
+                DE: OutputPin,
+            {
+                fn write(mut self, buffer: B) -> Transfer<R, B, Self> {
+                    // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                    // until the end of the transfer.
+                    let (ptr, len) = unsafe { buffer.static_read_buffer() };
+
+                    self.channel.set_peripheral_address(unsafe{ &(*$USARTX::ptr()).dr as *const _ as u32 }, false);
+
+                    self.channel.set_memory_address(ptr as u32, true);
+                    self.channel.set_transfer_length(len);
+
+                    atomic::compiler_fence(Ordering::Release);
+
+                    self.channel.ch().cr.modify(|_, w| { w
+                        .mem2mem() .clear_bit()
+                        .pl()      .medium()
+                        .msize()   .bits8()
+                        .psize()   .bits8()
+                        .circ()    .clear_bit()
+                        .dir()     .set_bit()
+                    });
+                    self.start();
+
+                    Transfer::r(buffer, self)
+                }
+            }
+        )+
+    }
+}
+
+rs485! {
+    USART1: (
+        Rs485Dma1,
+        dma1::C4,
+    ),
+    USART2: (
+        Rs485Dma2,
+        dma1::C7,
+    ),
+    USART3: (
+        Rs485Dma3,
+        dma1::C2,
+    ),
+}
@@ -0,0 +1,312 @@
+//! # USART in synchronous mode as SPI master
+//!
+//! USART1, USART2 and USART3 can generate a clock on their CK pin, which turns them into
+//! additional SPI master ports. The data is shifted out on TX (MOSI) and sampled on RX (MISO).
+//!
+//! The USART always transfers the least significant bit first. By default `UsartSpi` reverses the
+//! bits of every word in software so the bus behaves like a regular MSB first SPI bus. Use
+//! [`UsartSpi::bit_format`] to change this.
+//!
+//! The maximum clock frequency is 1/16 of the bus clock of the USART.
+//!
+//! | USART  | Remap | TX   | RX   | CK   |
+//! |:------:|:-----:|:----:|:----:|:----:|
+//! | USART1 |   0   | PA9  | PA10 | PA8  |
+//! | USART1 |   1   | PB6  | PB7  | PA8  |
+//! | USART2 |   0   | PA2  | PA3  | PA4  |
+//! | USART2 |   1   | PD5  | PD6  | PD7  |
+//! | USART3 |   0   | PB10 | PB11 | PB12 |
+//! | USART3 |   1   | PC10 | PC11 | PC12 |
+//! | USART3 |  0b11 | PD8  | PD9  | PD10 |
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! let mut gpioa = p.GPIOA.split();
+//!
+//! let pins = (
+//!     gpioa.pa9.into_alternate_push_pull(&mut gpioa.crh),
+//!     gpioa.pa10,
+//!     gpioa.pa8.into_alternate_push_pull(&mut gpioa.crh),
+//! );
+//!
+//! let mut spi = UsartSpi::usart1(p.USART1, pins, &mut afio.mapr, MODE_0, 1.mhz(), clocks);
+//!
+//! spi.write(&[0x01, 0x02, 0x03]).unwrap();
+//!  ```
+
+use crate::pac::{RCC, USART1, USART2, USART3};
+
+use crate::afio::MAPR;
+use crate::gpio::gpioa::{PA10, PA2, PA3, PA4, PA8, PA9};
+use crate::gpio::gpiob::{PB10, PB11, PB12, PB6, PB7};
+use crate::gpio::gpioc::{PC10, PC11, PC12};
+use crate::gpio::gpiod::{PD10, PD5, PD6, PD7, PD8, PD9};
+use crate::gpio::{Alternate, Floating, Input, PushPull};
+use crate::rcc::Clocks;
+use crate::spi::{Mode, Phase, Polarity, SpiBitFormat};
+use crate::time::Hertz;
+
+use super::{Error, Instance, UsartReadWrite};
+
+/// Pins of a USART used as SPI master, in `(tx, rx, ck)` order
+pub trait Pins<USART> {
+    const REMAP: u8;
+}
+
+impl Pins<USART1>
+    for (
+        PA9<Alternate<PushPull>>,
+        PA10<Input<Floating>>,
+        PA8<Alternate<PushPull>>,
+    )
+{
+    const REMAP: u8 = 0;
+}
+
+impl Pins<USART1>
+    for (
+        PB6<Alternate<PushPull>>,
+        PB7<Input<Floating>>,
+        PA8<Alternate<PushPull>>,
+    )
+{
+    const REMAP: u8 = 1;
+}
+
+impl Pins<USART2>
+    for (
+        PA2<Alternate<PushPull>>,
+        PA3<Input<Floating>>,
+        PA4<Alternate<PushPull>>,
+    )
+{
+    const REMAP: u8 = 0;
+}
+
+impl Pins<USART2>
+    for (
+        PD5<Alternate<PushPull>>,
+        PD6<Input<Floating>>,
+        PD7<Alternate<PushPull>>,
+    )
+{
+    const REMAP: u8 = 1;
+}
+
+impl Pins<USART3>
+    for (
+        PB10<Alternate<PushPull>>,
+        PB11<Input<Floating>>,
+        PB12<Alternate<PushPull>>,
+    )
+{
+    const REMAP: u8 = 0;
+}
+
+impl Pins<USART3>
+    for (
+        PC10<Alternate<PushPull>>,
+        PC11<Input<Floating>>,
+        PC12<Alternate<PushPull>>,
+    )
+{
+    const REMAP: u8 = 1;
+}
+
+impl Pins<USART3>
+    for (
+        PD8<Alternate<PushPull>>,
+        PD9<Input<Floating>>,
+        PD10<Alternate<PushPull>>,
+    )
+{
+    const REMAP: u8 = 0b11;
+}
+
+/// USART operating as SPI master in synchronous mode
+pub struct UsartSpi<USART, PINS> {
+    usart: USART,
+    pins: PINS,
+    bit_format: SpiBitFormat,
+}
+
+impl<USART, PINS> UsartSpi<USART, PINS>
+where
+    USART: Instance,
+{
+    fn init(self, mode: Mode, freq: Hertz, clocks: Clocks, remap: impl FnOnce()) -> Self {
+        // enable and reset $USARTX
+        let rcc = unsafe { &(*RCC::ptr()) };
+        USART::enable(rcc);
+        USART::reset(rcc);
+
+        remap();
+
+        // In synchronous mode the clock runs at the baud rate
+        let brr = USART::get_frequency(&clocks).0 / freq.0;
+        assert!(brr >= 16, "impossible clock frequency");
+        self.usart.brr.write(|w| unsafe { w.bits(brr) });
+
+        // CLKEN: enable the CK pin
+        // CPOL/CPHA: clock polarity and phase from the SPI mode
+        // LBCL: also output a clock pulse for the last data bit
+        // STOP: 1 stop bit, as required by synchronous mode
+        self.usart.cr2.write(|w| {
+            w.clken()
+                .set_bit()
+                .cpol()
+                .bit(mode.polarity == Polarity::IdleHigh)
+                .cpha()
+                .bit(mode.phase == Phase::CaptureOnSecondTransition)
+                .lbcl()
+                .set_bit()
+                .stop()
+                .stop1()
+        });
+
+        // SCEN, HDSEL and IREN must be kept cleared in synchronous mode
+        self.usart.cr3.reset();
+
+        // UE: enable USART
+        // RE: enable receiver
+        // TE: enable transceiver
+        // M, PCE: 8 data bits, no parity
+        self.usart
+            .cr1
+            .write(|w| w.ue().set_bit().re().set_bit().te().set_bit());
+
+        self
+    }
+
+    /// Select the bit order of the words on the bus
+    ///
+    /// The default is `MsbFirst`, which is achieved by reversing the bits of every word in
+    /// software.
+    pub fn bit_format(&mut self, format: SpiBitFormat) {
+        self.bit_format = format;
+    }
+
+    /// Returns ownership of the borrowed register handles
+    pub fn release(self) -> (USART, PINS) {
+        (self.usart, self.pins)
+    }
+
+    fn convert(&self, word: u8) -> u8 {
+        match self.bit_format {
+            SpiBitFormat::LsbFirst => word,
+            SpiBitFormat::MsbFirst => word.reverse_bits(),
+        }
+    }
+}
+
+macro_rules! usart_spi {
+    (
+        $(#[$meta:meta])*
+        $USARTX:ident: (
+            $usartX:ident,
+            $usartX_remap:ident,
+            $bit:ident,
+            $closure:expr,
+        ),
+    ) => {
+        $(#[$meta])*
+        impl<PINS> UsartSpi<$USARTX, PINS> {
+            /// Configures the USART as SPI master and creates the interface struct.
+            ///
+            /// The pin parameter tuple is `(tx, rx, ck)`.
+            pub fn $usartX<F>(
+                usart: $USARTX,
+                pins: PINS,
+                mapr: &mut MAPR,
+                mode: Mode,
+                freq: F,
+                clocks: Clocks,
+            ) -> Self
+            where
+                F: Into<Hertz>,
+                PINS: Pins<$USARTX>,
+            {
+                UsartSpi {
+                    usart,
+                    pins,
+                    bit_format: SpiBitFormat::MsbFirst,
+                }
+                .init(mode, freq.into(), clocks, || {
+                    // The remap field is only unsafe on some of the USARTs
+                    #[allow(unused_unsafe)]
+                    mapr.modify_mapr(|_, w| unsafe {
+                        #[allow(clippy::redundant_closure_call)]
+                        w.$usartX_remap().$bit(($closure)(PINS::REMAP))
+                    });
+                })
+            }
+        }
+    };
+}
+
+usart_spi! {
+    /// # USART1 functions
+    USART1: (
+        usart1,
+        usart1_remap,
+        bit,
+        |remap| remap == 1,
+    ),
+}
+usart_spi! {
+    /// # USART2 functions
+    USART2: (
+        usart2,
+        usart2_remap,
+        bit,
+        |remap| remap == 1,
+    ),
+}
+usart_spi! {
+    /// # USART3 functions
+    USART3: (
+        usart3,
+        usart3_remap,
+        bits,
+        |remap| remap,
+    ),
+}
+
+impl<USART, PINS> crate::hal::spi::FullDuplex<u8> for UsartSpi<USART, PINS>
+where
+    USART: Instance,
+{
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        let word = (&*self.usart).read()?;
+        Ok(self.convert(word))
+    }
+
+    fn send(&mut self, word: u8) -> nb::Result<(), Error> {
+        let sr = self.usart.sr.read();
+
+        if sr.ore().bit_is_set() {
+            // Clear the overrun flag by reading sr followed by dr
+            self.usart.dr.read();
+            Err(nb::Error::Other(Error::Overrun))
+        } else if sr.txe().bit_is_set() {
+            let word = self.convert(word);
+            self.usart.dr.write(|w| w.dr().bits(word.into()));
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<USART, PINS> crate::hal::blocking::spi::transfer::Default<u8> for UsartSpi<USART, PINS> where
+    USART: Instance
+{
+}
+
+impl<USART, PINS> crate::hal::blocking::spi::write::Default<u8> for UsartSpi<USART, PINS> where
+    USART: Instance
+{
+}
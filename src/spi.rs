@@ -12,6 +12,11 @@
   - `SPI2` can use `(PB13, PB14, PB15)`
   - `SPI3` can use `(PB3, PB4, PB5)` or only in connectivity line devices `(PC10, PC11, PC12)`
 
+  To operate as a slave, use the `Spi::spiX_slave` functions. They take an additional NSS pin, the
+  pin tuple is `(sck, miso, mosi, nss)` configured as `(Input<Floating>, Alternate<PushPull>, Input<Floating>, Input<Floating>)`.
+  The NSS pin is `PA4` for `SPI1` and `PA15` for remapped `SPI1`, `PB12` for `SPI2` and `PA15` for `SPI3`
+  (`PA4` if remapped).
+
 
   ## Initialisation example
 
@@ -46,17 +51,26 @@ use crate::dma::dma1;
 #[cfg(feature = "connectivity")]
 use crate::dma::dma2;
 use crate::dma::{Receive, RxDma, RxTxDma, Transfer, TransferPayload, Transmit, TxDma, R, W};
-use crate::gpio::gpioa::{PA5, PA6, PA7};
-use crate::gpio::gpiob::{PB13, PB14, PB15, PB3, PB4, PB5};
+use crate::gpio::gpioa::{PA15, PA4, PA5, PA6, PA7};
+use crate::gpio::gpiob::{PB12, PB13, PB14, PB15, PB3, PB4, PB5};
 #[cfg(feature = "connectivity")]
 use crate::gpio::gpioc::{PC10, PC11, PC12};
-use crate::gpio::{Alternate, Floating, Input, OpenDrain, PushPull};
+use crate::gpio::{Alternate, Floating, Input, OpenDrain, PullUp, PushPull};
 use crate::rcc::{Clocks, Enable, GetBusFreq, Reset};
 use crate::time::Hertz;
 
 use core::sync::atomic::{self, Ordering};
 use embedded_dma::{StaticReadBuffer, StaticWriteBuffer};
 
+pub mod device;
+pub use device::SpiDevice;
+pub mod half_duplex;
+pub use half_duplex::{SpiBidi, SpiRxOnly};
+#[cfg(any(all(feature = "stm32f103", feature = "high"), feature = "connectivity"))]
+pub mod i2s;
+#[cfg(any(all(feature = "stm32f103", feature = "high"), feature = "connectivity"))]
+pub use i2s::I2s;
+
 /// SPI error
 #[derive(Debug)]
 #[non_exhaustive]
@@ -67,6 +81,8 @@ pub enum Error {
     ModeFault,
     /// CRC error
     Crc,
+    /// Underrun occurred (I2S slave transmitter)
+    Underrun,
 }
 
 use core::marker::PhantomData;
@@ -79,9 +95,14 @@ mod sealed {
     pub trait Sck<REMAP> {}
     pub trait Miso<REMAP> {}
     pub trait Mosi<REMAP> {}
+    pub trait SlaveSck<REMAP> {}
+    pub trait SlaveMiso<REMAP> {}
+    pub trait SlaveMosi<REMAP> {}
+    pub trait Nss<REMAP> {}
+    pub trait NssOutput<REMAP> {}
 }
 pub use sealed::Remap;
-use sealed::{Miso, Mosi, Sck};
+use sealed::{Miso, Mosi, Nss, NssOutput, Sck, SlaveMiso, SlaveMosi, SlaveSck};
 
 pub trait Pins<REMAP> {}
 
@@ -93,6 +114,18 @@ where
 {
 }
 
+/// Pins of an SPI slave, in `(sck, miso, mosi, nss)` order
+pub trait SlavePins<REMAP> {}
+
+impl<REMAP, SCK, MISO, MOSI, NSS> SlavePins<REMAP> for (SCK, MISO, MOSI, NSS)
+where
+    SCK: SlaveSck<REMAP>,
+    MISO: SlaveMiso<REMAP>,
+    MOSI: SlaveMosi<REMAP>,
+    NSS: Nss<REMAP>,
+{
+}
+
 pub struct Spi<SPI, REMAP, PINS, FRAMESIZE> {
     spi: SPI,
     pins: PINS,
@@ -119,9 +152,11 @@ pub struct NoMosi;
 impl<REMAP> Sck<REMAP> for NoSck {}
 impl<REMAP> Miso<REMAP> for NoMiso {}
 impl<REMAP> Mosi<REMAP> for NoMosi {}
+impl<REMAP> SlaveMiso<REMAP> for NoMiso {}
+impl<REMAP> SlaveMosi<REMAP> for NoMosi {}
 
 macro_rules! remap {
-    ($name:ident, $SPIX:ident, $state:literal, $SCK:ident, $MISO:ident, $MOSI:ident) => {
+    ($name:ident, $SPIX:ident, $state:literal, $SCK:ident, $MISO:ident, $MOSI:ident, $NSS:ident) => {
         pub struct $name;
         impl Remap for $name {
             type Periph = $SPIX;
@@ -132,16 +167,23 @@ macro_rules! remap {
         impl Miso<$name> for $MISO<Input<Floating>> {}
         impl Mosi<$name> for $MOSI<Alternate<PushPull>> {}
         impl Mosi<$name> for $MOSI<Alternate<OpenDrain>> {}
+        impl SlaveSck<$name> for $SCK<Input<Floating>> {}
+        impl SlaveMiso<$name> for $MISO<Alternate<PushPull>> {}
+        impl SlaveMiso<$name> for $MISO<Alternate<OpenDrain>> {}
+        impl SlaveMosi<$name> for $MOSI<Input<Floating>> {}
+        impl Nss<$name> for $NSS<Input<Floating>> {}
+        impl Nss<$name> for $NSS<Input<PullUp>> {}
+        impl NssOutput<$name> for $NSS<Alternate<PushPull>> {}
     };
 }
 
-remap!(Spi1NoRemap, SPI1, false, PA5, PA6, PA7);
-remap!(Spi1Remap, SPI1, true, PB3, PB4, PB5);
-remap!(Spi2NoRemap, SPI2, false, PB13, PB14, PB15);
+remap!(Spi1NoRemap, SPI1, false, PA5, PA6, PA7, PA4);
+remap!(Spi1Remap, SPI1, true, PB3, PB4, PB5, PA15);
+remap!(Spi2NoRemap, SPI2, false, PB13, PB14, PB15, PB12);
 #[cfg(any(feature = "high", feature = "connectivity"))]
-remap!(Spi3NoRemap, SPI3, false, PB3, PB4, PB5);
+remap!(Spi3NoRemap, SPI3, false, PB3, PB4, PB5, PA15);
 #[cfg(feature = "connectivity")]
-remap!(Spi3Remap, SPI3, true, PC10, PC11, PC12);
+remap!(Spi3Remap, SPI3, true, PC10, PC11, PC12, PA4);
 
 pub trait Instance:
     crate::Sealed + Deref<Target = crate::pac::spi1::RegisterBlock> + Enable + Reset + GetBusFreq
@@ -242,6 +284,77 @@ impl<REMAP, PINS> Spi<SPI3, REMAP, PINS, u8> {
     }
 }
 
+impl<REMAP, PINS> Spi<SPI1, REMAP, PINS, u8> {
+    /**
+      Constructs an SPI slave using SPI1 in 8bit dataframe mode.
+
+      The pin parameter tuple (sck, miso, mosi, nss) should be `(PA5, PA6, PA7, PA4)` or `(PB3, PB4, PB5, PA15)` configured as `(Input<Floating>, Alternate<PushPull>, Input<Floating>, Input<Floating>)`.
+
+      You can also use `NoMiso` or `NoMosi` if you don't want to use the pins
+    */
+    pub fn spi1_slave(spi: SPI1, pins: PINS, mapr: &mut MAPR, mode: Mode) -> Self
+    where
+        REMAP: Remap<Periph = SPI1>,
+        PINS: SlavePins<REMAP>,
+    {
+        mapr.modify_mapr(|_, w| w.spi1_remap().bit(REMAP::REMAP));
+        Spi::<SPI1, _, _, u8>::_spi_slave(spi, pins, mode)
+    }
+}
+
+impl<REMAP, PINS> Spi<SPI2, REMAP, PINS, u8> {
+    /**
+      Constructs an SPI slave using SPI2 in 8bit dataframe mode.
+
+      The pin parameter tuple (sck, miso, mosi, nss) should be `(PB13, PB14, PB15, PB12)` configured as `(Input<Floating>, Alternate<PushPull>, Input<Floating>, Input<Floating>)`.
+
+      You can also use `NoMiso` or `NoMosi` if you don't want to use the pins
+    */
+    pub fn spi2_slave(spi: SPI2, pins: PINS, mode: Mode) -> Self
+    where
+        REMAP: Remap<Periph = SPI2>,
+        PINS: SlavePins<REMAP>,
+    {
+        Spi::<SPI2, _, _, u8>::_spi_slave(spi, pins, mode)
+    }
+}
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+impl<REMAP, PINS> Spi<SPI3, REMAP, PINS, u8> {
+    /**
+      Constructs an SPI slave using SPI3 in 8bit dataframe mode.
+
+      The pin parameter tuple (sck, miso, mosi, nss) should be `(PB3, PB4, PB5, PA15)` configured as `(Input<Floating>, Alternate<PushPull>, Input<Floating>, Input<Floating>)`.
+
+      You can also use `NoMiso` or `NoMosi` if you don't want to use the pins
+    */
+    #[cfg(not(feature = "connectivity"))]
+    pub fn spi3_slave(spi: SPI3, pins: PINS, mode: Mode) -> Self
+    where
+        REMAP: Remap<Periph = SPI3>,
+        PINS: SlavePins<REMAP>,
+    {
+        Spi::<SPI3, _, _, u8>::_spi_slave(spi, pins, mode)
+    }
+
+    /**
+      Constructs an SPI slave using SPI3 in 8bit dataframe mode.
+
+      The pin parameter tuple (sck, miso, mosi, nss) should be `(PB3, PB4, PB5, PA15)` or `(PC10, PC11, PC12, PA4)` configured as `(Input<Floating>, Alternate<PushPull>, Input<Floating>, Input<Floating>)`.
+
+      You can also use `NoMiso` or `NoMosi` if you don't want to use the pins
+    */
+    #[cfg(feature = "connectivity")]
+    pub fn spi3_slave(spi: SPI3, pins: PINS, mapr: &mut MAPR, mode: Mode) -> Self
+    where
+        REMAP: Remap<Periph = SPI3>,
+        PINS: SlavePins<REMAP>,
+    {
+        mapr.modify_mapr(|_, w| w.spi3_remap().bit(REMAP::REMAP));
+        Spi::<SPI3, _, _, u8>::_spi_slave(spi, pins, mode)
+    }
+}
+
 pub trait SpiReadWrite<T> {
     fn read_data_reg(&mut self) -> T;
     fn write_data_reg(&mut self, data: T);
@@ -329,6 +442,190 @@ where
             SpiBitFormat::MsbFirst => self.spi.cr1.modify(|_, w| w.lsbfirst().clear_bit()),
         }
     }
+
+    /// Lets the SPI drive its hardware NSS pin
+    ///
+    /// NSS is driven low as long as the SPI is enabled, which selects the only device on the bus.
+    /// While the SPI is disabled the pin is not driven, so it needs a pull-up resistor. Use [`deselect`](Spi::deselect) and [`select`](Spi::select) to mark the end and the start of
+    /// a transaction.
+    pub fn with_nss<NSS>(self, nss: NSS) -> Spi<SPI, REMAP, (PINS, NSS), FrameSize>
+    where
+        NSS: NssOutput<REMAP>,
+    {
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        // ssoe: NSS output enable
+        self.spi.cr2.modify(|_, w| w.ssoe().set_bit());
+        // ssm: NSS is controlled by the hardware
+        self.spi.cr1.modify(|_, w| w.ssm().clear_bit());
+        self.spi.cr1.modify(|_, w| w.spe().set_bit());
+        Spi {
+            spi: self.spi,
+            pins: (self.pins, nss),
+            _remap: PhantomData,
+            _framesize: PhantomData,
+        }
+    }
+
+    /// Enables the hardware CRC calculation with the given polynomial
+    ///
+    /// The CRC is 8 bit wide for 8 bit frames and 16 bit wide for 16 bit frames.
+    pub fn enable_crc(&mut self, polynomial: u16) {
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        self.spi.crcpr.write(|w| w.crcpoly().bits(polynomial));
+        self.spi.cr1.modify(|_, w| w.crcen().set_bit());
+        self.spi.cr1.modify(|_, w| w.spe().set_bit());
+    }
+
+    /// Disables the hardware CRC calculation
+    pub fn disable_crc(&mut self) {
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        self.spi.cr1.modify(|_, w| w.crcen().clear_bit());
+        self.spi.cr1.modify(|_, w| w.spe().set_bit());
+    }
+
+    /// Clears the transmit and receive CRC before a new frame
+    ///
+    /// The CRC can only be cleared while the SPI is disabled, so this must only be called between
+    /// frames. With the hardware NSS output of [`with_nss`](Spi::with_nss), disabling the SPI
+    /// releases NSS and enabling it again selects the device. Call this, `transfer_with_crc` and
+    /// `write_with_crc` while the device is deselected instead of after [`select`](Spi::select),
+    /// and end the frame with [`deselect`](Spi::deselect).
+    pub fn reset_crc(&mut self) {
+        // Disabling the SPI during a transfer would corrupt the last word
+        assert!(self.spi.sr.read().bsy().bit_is_clear());
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        self.spi.cr1.modify(|_, w| w.crcen().clear_bit());
+        self.spi.cr1.modify(|_, w| w.crcen().set_bit());
+        self.spi.cr1.modify(|_, w| w.spe().set_bit());
+    }
+
+    /// Finishes a frame with CRC, for example after a DMA transfer
+    ///
+    /// Waits until the CRC has been exchanged, drops the received CRC and returns `Error::Crc` if
+    /// it does not match the CRC of the received data.
+    pub fn check_crc(&mut self) -> Result<(), Error> {
+        while self.spi.sr.read().txe().bit_is_clear() {}
+        while self.spi.sr.read().bsy().bit_is_set() {}
+        let _ = self.read_data_reg();
+        if self.spi.sr.read().crcerr().bit_is_set() {
+            self.spi.sr.modify(|_, w| w.crcerr().clear_bit());
+            Err(Error::Crc)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Exchanges `words` followed by the CRC
+    ///
+    /// The CRC is cleared before the transfer. Returns `Error::Crc` if the received CRC does not
+    /// match.
+    pub fn transfer_with_crc(&mut self, words: &mut [FrameSize]) -> Result<(), Error> {
+        self.reset_crc();
+        let len = words.len();
+        for (i, word) in words.iter_mut().enumerate() {
+            while self.spi.sr.read().txe().bit_is_clear() {}
+            self.write_data_reg(*word);
+            if i + 1 == len {
+                // crcnext: send the CRC after the last word
+                self.spi.cr1.modify(|_, w| w.crcnext().set_bit());
+            }
+            loop {
+                let sr = self.spi.sr.read();
+                if sr.ovr().bit_is_set() {
+                    return Err(Error::Overrun);
+                } else if sr.modf().bit_is_set() {
+                    return Err(Error::ModeFault);
+                } else if sr.rxne().bit_is_set() {
+                    break;
+                }
+            }
+            *word = self.read_data_reg();
+        }
+        // Wait for the received CRC
+        while self.spi.sr.read().rxne().bit_is_clear() {}
+        self.check_crc()
+    }
+
+    /// Sends `words` followed by the CRC
+    ///
+    /// The CRC is cleared before the transfer. The received data is dropped.
+    pub fn write_with_crc(&mut self, words: &[FrameSize]) -> Result<(), Error> {
+        self.reset_crc();
+        let len = words.len();
+        for (i, word) in words.iter().enumerate() {
+            while self.spi.sr.read().txe().bit_is_clear() {}
+            self.write_data_reg(*word);
+            if i + 1 == len {
+                // crcnext: send the CRC after the last word
+                self.spi.cr1.modify(|_, w| w.crcnext().set_bit());
+            }
+            // Keep the receiver from overrunning
+            while self.spi.sr.read().rxne().bit_is_clear() {}
+            let _ = self.read_data_reg();
+        }
+        // Wait for the CRC to be sent
+        while self.spi.sr.read().rxne().bit_is_clear() {}
+        self.check_crc()
+    }
+
+    /// Changes the mode and the frequency
+    ///
+    /// Waits until the last transfer is complete, then disables the SPI while CR1 is changed.
+    pub fn reconfigure<F>(&mut self, mode: Mode, freq: F, clocks: &Clocks)
+    where
+        F: Into<Hertz>,
+    {
+        // Disabling the SPI during a transfer would corrupt the last word
+        while self.spi.sr.read().txe().bit_is_clear() {}
+        while self.spi.sr.read().bsy().bit_is_set() {}
+
+        let br = baud_rate_divider::<SPI>(freq.into(), clocks);
+        // With hardware NSS output the enable bit also selects the device, so it is restored
+        let enabled = self.spi.cr1.read().spe().bit_is_set();
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        self.spi.cr1.modify(|_, w| {
+            w.cpha()
+                .bit(mode.phase == Phase::CaptureOnSecondTransition)
+                .cpol()
+                .bit(mode.polarity == Polarity::IdleHigh)
+                .br()
+                .bits(br)
+        });
+        self.spi.cr1.modify(|_, w| w.spe().bit(enabled));
+    }
+}
+
+impl<SPI, REMAP, PINS, NSS, FrameSize> Spi<SPI, REMAP, (PINS, NSS), FrameSize>
+where
+    SPI: Instance,
+{
+    /// Selects the device by enabling the SPI, which drives NSS low
+    pub fn select(&mut self) {
+        self.spi.cr1.modify(|_, w| w.spe().set_bit());
+    }
+
+    /// Waits until the last transfer is complete and deselects the device by disabling the SPI,
+    /// which releases NSS
+    pub fn deselect(&mut self) {
+        while self.spi.sr.read().txe().bit_is_clear() {}
+        while self.spi.sr.read().bsy().bit_is_set() {}
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+    }
+}
+
+/// Returns the baud rate control bits for the highest frequency not above `freq`
+fn baud_rate_divider<SPI: Instance>(freq: Hertz, clocks: &Clocks) -> u8 {
+    match SPI::get_frequency(clocks).0 / freq.0 {
+        0 => unreachable!(),
+        1..=2 => 0b000,
+        3..=5 => 0b001,
+        6..=11 => 0b010,
+        12..=23 => 0b011,
+        24..=47 => 0b100,
+        48..=95 => 0b101,
+        96..=191 => 0b110,
+        _ => 0b111,
+    }
 }
 
 impl<SPI, REMAP, PINS> Spi<SPI, REMAP, PINS, u8>
@@ -344,17 +641,7 @@ where
         // disable SS output
         spi.cr2.write(|w| w.ssoe().clear_bit());
 
-        let br = match SPI::get_frequency(&clocks).0 / freq.0 {
-            0 => unreachable!(),
-            1..=2 => 0b000,
-            3..=5 => 0b001,
-            6..=11 => 0b010,
-            12..=23 => 0b011,
-            24..=47 => 0b100,
-            48..=95 => 0b101,
-            96..=191 => 0b110,
-            _ => 0b111,
-        };
+        let br = baud_rate_divider::<SPI>(freq, &clocks);
 
         spi.cr1.write(|w| {
             w
@@ -400,6 +687,53 @@ where
             _framesize: PhantomData,
         }
     }
+    fn _spi_slave(spi: SPI, pins: PINS, mode: Mode) -> Self {
+        // enable or reset SPI
+        let rcc = unsafe { &(*RCC::ptr()) };
+        SPI::enable(rcc);
+        SPI::reset(rcc);
+
+        // disable SS output
+        spi.cr2.write(|w| w.ssoe().clear_bit());
+
+        spi.cr1.write(|w| {
+            w
+                // clock phase from config
+                .cpha()
+                .bit(mode.phase == Phase::CaptureOnSecondTransition)
+                // clock polarity from config
+                .cpol()
+                .bit(mode.polarity == Polarity::IdleHigh)
+                // mstr: slave configuration
+                .mstr()
+                .clear_bit()
+                // lsbfirst: MSB first
+                .lsbfirst()
+                .clear_bit()
+                // ssm: the slave is selected by the NSS pin
+                .ssm()
+                .clear_bit()
+                // dff: 8 bit frames
+                .dff()
+                .clear_bit()
+                // bidimode: 2-line unidirectional
+                .bidimode()
+                .clear_bit()
+                // both TX and RX are used
+                .rxonly()
+                .clear_bit()
+                // spe: enable the SPI bus
+                .spe()
+                .set_bit()
+        });
+
+        Spi {
+            spi,
+            pins,
+            _remap: PhantomData,
+            _framesize: PhantomData,
+        }
+    }
     /// Converts from 8bit dataframe to 16bit.
     pub fn frame_size_16bit(self) -> Spi<SPI, REMAP, PINS, u16> {
         self.spi.cr1.modify(|_, w| w.spe().clear_bit());
@@ -576,6 +910,21 @@ macro_rules! spi_dma {
         }
 
         impl<REMAP, PINS> SpiTxDma<$SPIi, REMAP, PINS, $TCi> {
+            /// Changes the mode and the frequency between transfers, see [`Spi::reconfigure`]
+            pub fn reconfigure<F>(&mut self, mode: Mode, freq: F, clocks: &Clocks)
+            where
+                F: Into<Hertz>,
+            {
+                self.payload.reconfigure(mode, freq, clocks)
+            }
+            /// Clears the CRC before the next transfer, see [`Spi::reset_crc`]
+            pub fn reset_crc(&mut self) {
+                self.payload.reset_crc()
+            }
+            /// Finishes a frame with CRC after the transfer, see [`Spi::check_crc`]
+            pub fn check_crc(&mut self) -> Result<(), Error> {
+                self.payload.check_crc()
+            }
             pub fn release(self) -> (Spi<$SPIi, REMAP, PINS, u8>, $TCi) {
                 let SpiTxDma { payload, channel } = self;
                 payload.spi.cr2.modify(|_, w| w.txdmaen().clear_bit());
@@ -584,6 +933,21 @@ macro_rules! spi_dma {
         }
 
         impl<REMAP, PINS> SpiRxDma<$SPIi, REMAP, PINS, $RCi> {
+            /// Changes the mode and the frequency between transfers, see [`Spi::reconfigure`]
+            pub fn reconfigure<F>(&mut self, mode: Mode, freq: F, clocks: &Clocks)
+            where
+                F: Into<Hertz>,
+            {
+                self.payload.reconfigure(mode, freq, clocks)
+            }
+            /// Clears the CRC before the next transfer, see [`Spi::reset_crc`]
+            pub fn reset_crc(&mut self) {
+                self.payload.reset_crc()
+            }
+            /// Finishes a frame with CRC after the transfer, see [`Spi::check_crc`]
+            pub fn check_crc(&mut self) -> Result<(), Error> {
+                self.payload.check_crc()
+            }
             pub fn release(self) -> (Spi<$SPIi, REMAP, PINS, u8>, $RCi) {
                 let SpiRxDma { payload, channel } = self;
                 payload.spi.cr2.modify(|_, w| w.rxdmaen().clear_bit());
@@ -592,6 +956,21 @@ macro_rules! spi_dma {
         }
 
         impl<REMAP, PINS> SpiRxTxDma<$SPIi, REMAP, PINS, $RCi, $TCi> {
+            /// Changes the mode and the frequency between transfers, see [`Spi::reconfigure`]
+            pub fn reconfigure<F>(&mut self, mode: Mode, freq: F, clocks: &Clocks)
+            where
+                F: Into<Hertz>,
+            {
+                self.payload.reconfigure(mode, freq, clocks)
+            }
+            /// Clears the CRC before the next transfer, see [`Spi::reset_crc`]
+            pub fn reset_crc(&mut self) {
+                self.payload.reset_crc()
+            }
+            /// Finishes a frame with CRC after the transfer, see [`Spi::check_crc`]
+            pub fn check_crc(&mut self) -> Result<(), Error> {
+                self.payload.check_crc()
+            }
             pub fn release(self) -> (Spi<$SPIi, REMAP, PINS, u8>, $RCi, $TCi) {
                 let SpiRxTxDma {
                     payload,
@@ -0,0 +1,146 @@
+//! # Shared SPI bus
+//!
+//! Several devices with their own chip select pins can share one SPI master. Each [`SpiDevice`]
+//! owns the chip select pin of its device and borrows the [`Spi`] through a [`SharedBus`], which is
+//! either a `RefCell` or a `cortex_m::interrupt::Mutex<RefCell<_>>` when the bus is also used from
+//! interrupt handlers.
+//!
+//! Before every transaction the device sets up the mode and the frequency of its device, then
+//! drives the chip select pin low for the duration of the transaction.
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! let spi = Spi::spi1(dp.SPI1, pins, &mut afio.mapr, MODE_0, 1.mhz(), clocks);
+//! let bus = RefCell::new(spi);
+//!
+//! let mut flash = SpiDevice::new(&bus, flash_cs, MODE_0, 20.mhz(), clocks);
+//! let mut imu = SpiDevice::new(&bus, imu_cs, MODE_3, 1.mhz(), clocks);
+//!
+//! flash.write(&[0x06])?;
+//! let id = imu.transfer(&mut [0x80 | 0x75, 0])?;
+//!  ```
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use cortex_m::interrupt::{self, Mutex};
+
+use crate::hal::blocking::spi::{Transfer, Write};
+use crate::hal::digital::v2::OutputPin;
+use crate::rcc::Clocks;
+use crate::time::Hertz;
+
+use super::{Error, Instance, Mode, Spi};
+
+/// Exclusive access to a bus which is shared between several drivers
+pub trait SharedBus {
+    type Bus;
+
+    /// Calls `f` with exclusive access to the bus
+    fn lock<R>(&self, f: impl FnOnce(&mut Self::Bus) -> R) -> R;
+}
+
+impl<BUS> SharedBus for RefCell<BUS> {
+    type Bus = BUS;
+
+    fn lock<R>(&self, f: impl FnOnce(&mut BUS) -> R) -> R {
+        f(&mut self.borrow_mut())
+    }
+}
+
+impl<BUS> SharedBus for Mutex<RefCell<BUS>> {
+    type Bus = BUS;
+
+    fn lock<R>(&self, f: impl FnOnce(&mut BUS) -> R) -> R {
+        interrupt::free(|cs| f(&mut self.borrow(cs).borrow_mut()))
+    }
+}
+
+/// A device on a shared SPI bus
+pub struct SpiDevice<'a, BUS, CS> {
+    bus: &'a BUS,
+    cs: CS,
+    mode: Mode,
+    freq: Hertz,
+    clocks: Clocks,
+}
+
+impl<'a, BUS, CS> SpiDevice<'a, BUS, CS>
+where
+    CS: OutputPin<Error = Infallible>,
+{
+    /// Creates a device which is selected by `cs` and uses the given mode and frequency
+    ///
+    /// The chip select pin is driven high, deselecting the device.
+    pub fn new<F>(bus: &'a BUS, mut cs: CS, mode: Mode, freq: F, clocks: Clocks) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        cs.set_high().ok();
+        SpiDevice {
+            bus,
+            cs,
+            mode,
+            freq: freq.into(),
+            clocks,
+        }
+    }
+
+    /// Returns the chip select pin
+    pub fn release(self) -> CS {
+        self.cs
+    }
+
+    /// Locks the bus, configures it for this device and runs `f` with the device selected
+    fn transaction<SPI, REMAP, PINS, R>(
+        &mut self,
+        f: impl FnOnce(&mut Spi<SPI, REMAP, PINS, u8>) -> Result<R, Error>,
+    ) -> Result<R, Error>
+    where
+        BUS: SharedBus<Bus = Spi<SPI, REMAP, PINS, u8>>,
+        SPI: Instance,
+    {
+        let SpiDevice {
+            bus,
+            cs,
+            mode,
+            freq,
+            clocks,
+        } = self;
+        bus.lock(|spi| {
+            spi.reconfigure(*mode, *freq, clocks);
+            cs.set_low().ok();
+            let result = f(spi);
+            cs.set_high().ok();
+            result
+        })
+    }
+}
+
+impl<'a, BUS, CS, SPI, REMAP, PINS> Transfer<u8> for SpiDevice<'a, BUS, CS>
+where
+    BUS: SharedBus<Bus = Spi<SPI, REMAP, PINS, u8>>,
+    SPI: Instance,
+    CS: OutputPin<Error = Infallible>,
+{
+    type Error = Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Error> {
+        self.transaction(|spi| spi.transfer(words).map(|_| ()))?;
+        Ok(words)
+    }
+}
+
+impl<'a, BUS, CS, SPI, REMAP, PINS> Write<u8> for SpiDevice<'a, BUS, CS>
+where
+    BUS: SharedBus<Bus = Spi<SPI, REMAP, PINS, u8>>,
+    SPI: Instance,
+    CS: OutputPin<Error = Infallible>,
+{
+    type Error = Error;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        self.transaction(|spi| spi.write(words))
+    }
+}
@@ -0,0 +1,332 @@
+//! # Bidirectional 3-wire and receive-only SPI master
+//!
+//! [`SpiBidi`] uses a single bidirectional data line on the MOSI pin, the pin tuple is
+//! `(sck, mosi)`. [`SpiRxOnly`] only receives on the MISO pin, the pin tuple is `(sck, miso)`.
+//!
+//! In both receive modes the master generates the clock as long as the SPI is enabled. To stop
+//! after the last requested word the SPI is disabled while that word is being received, as
+//! described in section "Disabling the SPI" of RM0008.
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! let pins = (
+//!     gpiob.pb13.into_alternate_push_pull(&mut gpiob.crh),
+//!     gpiob.pb15.into_alternate_push_pull(&mut gpiob.crh),
+//! );
+//! let mut spi = SpiBidi::spi2(dp.SPI2, pins, MODE_0, 1.mhz(), clocks);
+//!
+//! spi.write(&[0x80 | 0x0f])?;
+//! let mut id = [0];
+//! spi.read(&mut id)?;
+//!  ```
+
+use core::marker::PhantomData;
+use core::ptr;
+
+use cortex_m::asm::delay;
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+use crate::pac::SPI3;
+use crate::pac::{RCC, SPI1, SPI2};
+
+use crate::afio::MAPR;
+use crate::rcc::Clocks;
+use crate::time::Hertz;
+
+use super::sealed::{Miso, Mosi, Sck};
+use super::{baud_rate_divider, Error, Instance, Mode, Phase, Polarity, Remap};
+
+/// Pins of a bidirectional SPI master, in `(sck, mosi)` order
+pub trait BidiPins<REMAP> {}
+
+impl<REMAP, SCK, MOSI> BidiPins<REMAP> for (SCK, MOSI)
+where
+    SCK: Sck<REMAP>,
+    MOSI: Mosi<REMAP>,
+{
+}
+
+/// Pins of a receive-only SPI master, in `(sck, miso)` order
+pub trait RxOnlyPins<REMAP> {}
+
+impl<REMAP, SCK, MISO> RxOnlyPins<REMAP> for (SCK, MISO)
+where
+    SCK: Sck<REMAP>,
+    MISO: Miso<REMAP>,
+{
+}
+
+/// SPI master with a single bidirectional data line
+pub struct SpiBidi<SPI, REMAP, PINS> {
+    spi: SPI,
+    pins: PINS,
+    /// Duration of one SPI clock cycle in CPU cycles
+    clock_cycles: u32,
+    _remap: PhantomData<REMAP>,
+}
+
+/// SPI master which only receives
+pub struct SpiRxOnly<SPI, REMAP, PINS> {
+    spi: SPI,
+    pins: PINS,
+    /// Duration of one SPI clock cycle in CPU cycles
+    clock_cycles: u32,
+    _remap: PhantomData<REMAP>,
+}
+
+/// Enables, resets and configures the SPI as master, returns the length of a clock cycle in CPU
+/// cycles
+fn init<SPI: Instance>(spi: &SPI, mode: Mode, freq: Hertz, clocks: &Clocks, bidi: bool) -> u32 {
+    // enable or reset SPI
+    let rcc = unsafe { &(*RCC::ptr()) };
+    SPI::enable(rcc);
+    SPI::reset(rcc);
+
+    // disable SS output
+    spi.cr2.write(|w| w.ssoe().clear_bit());
+
+    let br = baud_rate_divider::<SPI>(freq, clocks);
+
+    spi.cr1.write(|w| {
+        w
+            // clock phase from config
+            .cpha()
+            .bit(mode.phase == Phase::CaptureOnSecondTransition)
+            // clock polarity from config
+            .cpol()
+            .bit(mode.polarity == Polarity::IdleHigh)
+            // mstr: master configuration
+            .mstr()
+            .set_bit()
+            // baudrate value
+            .br()
+            .bits(br)
+            // lsbfirst: MSB first
+            .lsbfirst()
+            .clear_bit()
+            // ssm: enable software slave management (NSS pin free for other uses)
+            .ssm()
+            .set_bit()
+            // ssi: set nss high = master mode
+            .ssi()
+            .set_bit()
+            // dff: 8 bit frames
+            .dff()
+            .clear_bit()
+            // bidimode: 1-line bidirectional or 2-line unidirectional
+            .bidimode()
+            .bit(bidi)
+            // bidioe: the bidirectional line idles as output, which does not generate a clock
+            .bidioe()
+            .bit(bidi)
+            // rxonly: receive-only unless bidirectional
+            .rxonly()
+            .bit(!bidi)
+            // spe: the receive-only master is only enabled while reading
+            .spe()
+            .bit(bidi)
+    });
+
+    (2 << br) * (clocks.hclk().0 / SPI::get_frequency(clocks).0)
+}
+
+/// Reads `words` in a master receive mode, the clock starts as soon as the SPI is enabled
+fn receive<SPI: Instance>(spi: &SPI, clock_cycles: u32, words: &mut [u8]) -> Result<(), Error> {
+    let len = words.len();
+    if len == 0 {
+        return Ok(());
+    }
+
+    spi.cr1.modify(|_, w| w.spe().set_bit());
+
+    // The SPI has to be disabled while the last word is received, one clock cycle after it has
+    // started
+    if len == 1 {
+        delay(clock_cycles);
+        spi.cr1.modify(|_, w| w.spe().clear_bit());
+    }
+
+    for (i, word) in words.iter_mut().enumerate() {
+        loop {
+            let sr = spi.sr.read();
+            if sr.ovr().bit_is_set() {
+                spi.cr1.modify(|_, w| w.spe().clear_bit());
+                // Clear the overrun flag by reading dr followed by sr
+                let _ = spi.dr.read();
+                let _ = spi.sr.read();
+                return Err(Error::Overrun);
+            }
+            if sr.rxne().bit_is_set() {
+                break;
+            }
+        }
+        // NOTE(read_volatile) read only 1 byte (the svd2rust API only allows
+        // reading a half-word)
+        *word = unsafe { ptr::read_volatile(&spi.dr as *const _ as *const u8) };
+
+        if i + 2 == len {
+            delay(clock_cycles);
+            spi.cr1.modify(|_, w| w.spe().clear_bit());
+        }
+    }
+
+    Ok(())
+}
+
+impl<SPI, REMAP, PINS> SpiBidi<SPI, REMAP, PINS>
+where
+    SPI: Instance,
+{
+    /// Sends `words` on the bidirectional data line
+    pub fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        // Turning the line around disables the SPI, so it has to be enabled again
+        self.spi
+            .cr1
+            .modify(|_, w| w.bidioe().set_bit().spe().set_bit());
+
+        for word in words {
+            loop {
+                let sr = self.spi.sr.read();
+                if sr.modf().bit_is_set() {
+                    return Err(Error::ModeFault);
+                }
+                if sr.txe().bit_is_set() {
+                    break;
+                }
+            }
+            // The frame format is 8 bits, only the low byte is sent
+            self.spi.dr.write(|w| w.dr().bits(u16::from(*word)));
+        }
+
+        // Wait until the last word has been sent
+        while self.spi.sr.read().txe().bit_is_clear() {}
+        while self.spi.sr.read().bsy().bit_is_set() {}
+        Ok(())
+    }
+
+    /// Receives `words` on the bidirectional data line
+    pub fn read(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        // Switching to input would start the clock right away
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        self.spi.cr1.modify(|_, w| w.bidioe().clear_bit());
+        let result = receive(&self.spi, self.clock_cycles, words);
+
+        // The line idles as output, the clock is only started by writing data
+        self.spi.cr1.modify(|_, w| w.bidioe().set_bit());
+        self.spi.cr1.modify(|_, w| w.spe().set_bit());
+        result
+    }
+
+    /// Returns ownership of the SPI peripheral and the pins
+    pub fn release(self) -> (SPI, PINS) {
+        (self.spi, self.pins)
+    }
+}
+
+impl<SPI, REMAP, PINS> crate::hal::blocking::spi::Write<u8> for SpiBidi<SPI, REMAP, PINS>
+where
+    SPI: Instance,
+{
+    type Error = Error;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        SpiBidi::write(self, words)
+    }
+}
+
+impl<SPI, REMAP, PINS> SpiRxOnly<SPI, REMAP, PINS>
+where
+    SPI: Instance,
+{
+    /// Generates the clock for `words.len()` words and receives them
+    pub fn read(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        receive(&self.spi, self.clock_cycles, words)
+    }
+
+    /// Returns ownership of the SPI peripheral and the pins
+    pub fn release(self) -> (SPI, PINS) {
+        (self.spi, self.pins)
+    }
+}
+
+macro_rules! half_duplex {
+    (
+        $(#[$meta:meta])*
+        $SPIX:ident: ($spiX:ident $(, $spiX_remap:ident)?)
+    ) => {
+        $(#[$meta])*
+        impl<REMAP, PINS> SpiBidi<$SPIX, REMAP, PINS> {
+            /**
+              Constructs a bidirectional SPI master on the MOSI pin.
+
+              The pin parameter tuple is `(sck, mosi)`, both configured as `Alternate<PushPull>`.
+            */
+            pub fn $spiX<F>(
+                spi: $SPIX,
+                pins: PINS,
+                $(mapr: half_duplex!(@mapr $spiX_remap),)?
+                mode: Mode,
+                freq: F,
+                clocks: Clocks,
+            ) -> Self
+            where
+                F: Into<Hertz>,
+                REMAP: Remap<Periph = $SPIX>,
+                PINS: BidiPins<REMAP>,
+            {
+                $(mapr.modify_mapr(|_, w| w.$spiX_remap().bit(REMAP::REMAP));)?
+                let clock_cycles = init(&spi, mode, freq.into(), &clocks, true);
+                SpiBidi {
+                    spi,
+                    pins,
+                    clock_cycles,
+                    _remap: PhantomData,
+                }
+            }
+        }
+
+        $(#[$meta])*
+        impl<REMAP, PINS> SpiRxOnly<$SPIX, REMAP, PINS> {
+            /**
+              Constructs a receive-only SPI master.
+
+              The pin parameter tuple is `(sck, miso)` configured as `(Alternate<PushPull>, Input<Floating>)`.
+            */
+            pub fn $spiX<F>(
+                spi: $SPIX,
+                pins: PINS,
+                $(mapr: half_duplex!(@mapr $spiX_remap),)?
+                mode: Mode,
+                freq: F,
+                clocks: Clocks,
+            ) -> Self
+            where
+                F: Into<Hertz>,
+                REMAP: Remap<Periph = $SPIX>,
+                PINS: RxOnlyPins<REMAP>,
+            {
+                $(mapr.modify_mapr(|_, w| w.$spiX_remap().bit(REMAP::REMAP));)?
+                let clock_cycles = init(&spi, mode, freq.into(), &clocks, false);
+                SpiRxOnly {
+                    spi,
+                    pins,
+                    clock_cycles,
+                    _remap: PhantomData,
+                }
+            }
+        }
+    };
+    (@mapr $spiX_remap:ident) => { &mut MAPR };
+}
+
+half_duplex!(SPI1: (spi1, spi1_remap));
+half_duplex!(SPI2: (spi2));
+half_duplex!(
+    #[cfg(all(feature = "high", not(feature = "connectivity")))]
+    SPI3: (spi3)
+);
+half_duplex!(
+    #[cfg(feature = "connectivity")]
+    SPI3: (spi3, spi3_remap)
+);
@@ -0,0 +1,611 @@
+//! # Inter-IC Sound (I2S)
+//!
+//! On high-density STM32F103 and connectivity line devices SPI2 and SPI3 can run as I2S interface. To
+//! construct the I2S instances, use the `I2s::i2sX` functions.
+//!
+//! The pin parameter tuple is `(ws, ck, sd, mck)`. As master `ws` and `ck` are configured as
+//! `Alternate<PushPull>`, as slave as `Input<Floating>`. `sd` is `Alternate<PushPull>` when
+//! transmitting and `Input<Floating>` when receiving. Use `NoMck` if the master clock is not needed,
+//! otherwise the master clock output is enabled.
+//!
+//! - `SPI2` uses `(PB12, PB13, PB15, PC6)`
+//! - `SPI3` uses `(PA15, PB3, PB5, PC7)` or only in connectivity line devices `(PA4, PC10, PC12, PC7)`
+//!
+//! The sample rate is derived from [`Clocks::i2sclk`], which is SYSCLK unless PLL3 has been set up
+//! with `CFGR::i2sclk` on connectivity line devices. The actual sample rate is returned by
+//! [`I2s::sample_rate`].
+//!
+//! Data is transferred in half-words, alternating between the left and the right channel. With 24
+//! and 32 bit data each sample takes two half-words, the most significant one first.
+//!
+//! ## Example usage:
+//!
+//!  ```rust
+//! let pins = (
+//!     gpiob.pb12.into_alternate_push_pull(&mut gpiob.crh),
+//!     gpiob.pb13.into_alternate_push_pull(&mut gpiob.crh),
+//!     gpiob.pb15.into_alternate_push_pull(&mut gpiob.crh),
+//!     gpioc.pc6.into_alternate_push_pull(&mut gpioc.crl),
+//! );
+//! let config = Config::default().sample_rate(48.khz());
+//! let mut i2s = I2s::i2s2(dp.SPI2, pins, config, clocks);
+//!
+//! i2s.enable();
+//! i2s.write(&[left, right])?;
+//!  ```
+
+use core::sync::atomic::{self, Ordering};
+
+use embedded_dma::StaticWriteBuffer;
+use nb::block;
+
+use crate::dma::{dma1, dma2, CircBuffer, Receive, RxDma, TransferPayload, Transmit, TxDma};
+use crate::gpio::gpioa::PA15;
+use crate::gpio::gpiob::{PB12, PB13, PB15, PB3, PB5};
+use crate::gpio::gpioc::{PC6, PC7};
+use crate::gpio::{Alternate, Floating, Input, PushPull};
+use crate::pac::{RCC, SPI2, SPI3};
+use crate::rcc::Clocks;
+use crate::time::Hertz;
+#[cfg(feature = "connectivity")]
+use crate::{
+    afio::MAPR,
+    gpio::{gpioa::PA4, gpioc::PC10, gpioc::PC12},
+};
+
+#[cfg(feature = "connectivity")]
+use super::Spi3Remap;
+use super::{Error, Instance, Remap, Spi2NoRemap, Spi3NoRemap};
+
+mod sealed {
+    pub trait Ws<REMAP> {}
+    pub trait Ck<REMAP> {}
+    pub trait Sd<REMAP> {}
+    pub trait Mck<REMAP> {
+        const ENABLED: bool;
+    }
+}
+use sealed::{Ck, Mck, Sd, Ws};
+
+/// Pins of an I2S interface, in `(ws, ck, sd, mck)` order
+pub trait Pins<REMAP> {
+    #[doc(hidden)]
+    const MCK: bool;
+}
+
+impl<REMAP, WS, CK, SD, MCK> Pins<REMAP> for (WS, CK, SD, MCK)
+where
+    WS: Ws<REMAP>,
+    CK: Ck<REMAP>,
+    SD: Sd<REMAP>,
+    MCK: Mck<REMAP>,
+{
+    const MCK: bool = MCK::ENABLED;
+}
+
+/// A filler type for when the master clock output is not used
+pub struct NoMck;
+
+impl<REMAP> Mck<REMAP> for NoMck {
+    const ENABLED: bool = false;
+}
+
+macro_rules! pins {
+    ($REMAP:ident, $WS:ident, $CK:ident, $SD:ident, $MCK:ident) => {
+        impl Ws<$REMAP> for $WS<Alternate<PushPull>> {}
+        impl Ws<$REMAP> for $WS<Input<Floating>> {}
+        impl Ck<$REMAP> for $CK<Alternate<PushPull>> {}
+        impl Ck<$REMAP> for $CK<Input<Floating>> {}
+        impl Sd<$REMAP> for $SD<Alternate<PushPull>> {}
+        impl Sd<$REMAP> for $SD<Input<Floating>> {}
+        impl Mck<$REMAP> for $MCK<Alternate<PushPull>> {
+            const ENABLED: bool = true;
+        }
+    };
+}
+
+pins!(Spi2NoRemap, PB12, PB13, PB15, PC6);
+pins!(Spi3NoRemap, PA15, PB3, PB5, PC7);
+#[cfg(feature = "connectivity")]
+pins!(Spi3Remap, PA4, PC10, PC12, PC7);
+
+/// Operating mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum I2sMode {
+    MasterTx,
+    MasterRx,
+    SlaveTx,
+    SlaveRx,
+}
+
+impl I2sMode {
+    fn is_master(self) -> bool {
+        matches!(self, I2sMode::MasterTx | I2sMode::MasterRx)
+    }
+}
+
+/// I2S standard
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Standard {
+    /// I2S Philips standard
+    Philips,
+    /// MSB justified (left justified) standard
+    Msb,
+    /// LSB justified (right justified) standard
+    Lsb,
+    /// PCM standard with a word select pulse of one clock cycle
+    PcmShort,
+    /// PCM standard with a word select pulse of 13 clock cycles
+    PcmLong,
+}
+
+/// Data length and channel length
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataFormat {
+    /// 16 bit data in a 16 bit channel
+    Data16Channel16,
+    /// 16 bit data in a 32 bit channel
+    Data16Channel32,
+    /// 24 bit data in a 32 bit channel
+    Data24Channel32,
+    /// 32 bit data in a 32 bit channel
+    Data32Channel32,
+}
+
+/// Steady state of the clock
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClockPolarity {
+    IdleLow,
+    IdleHigh,
+}
+
+/// Channel of the current half-word
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    Left,
+    Right,
+}
+
+pub struct Config {
+    pub mode: I2sMode,
+    pub standard: Standard,
+    pub data_format: DataFormat,
+    pub clock_polarity: ClockPolarity,
+    /// Only used in master mode
+    pub sample_rate: Hertz,
+}
+
+impl Config {
+    pub fn mode(mut self, mode: I2sMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    pub fn data_format(mut self, data_format: DataFormat) -> Self {
+        self.data_format = data_format;
+        self
+    }
+
+    pub fn clock_polarity(mut self, clock_polarity: ClockPolarity) -> Self {
+        self.clock_polarity = clock_polarity;
+        self
+    }
+
+    pub fn sample_rate<F>(mut self, sample_rate: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.sample_rate = sample_rate.into();
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            mode: I2sMode::MasterTx,
+            standard: Standard::Philips,
+            data_format: DataFormat::Data16Channel16,
+            clock_polarity: ClockPolarity::IdleLow,
+            sample_rate: Hertz(48_000),
+        }
+    }
+}
+
+/// SPI2 or SPI3 in I2S mode
+pub struct I2s<SPI, REMAP, PINS> {
+    spi: SPI,
+    pins: PINS,
+    mode: I2sMode,
+    sample_rate: Option<Hertz>,
+    _remap: core::marker::PhantomData<REMAP>,
+}
+
+/// Returns the I2SDIV and ODD values closest to `sample_rate` and the resulting sample rate
+fn prescaler(i2sclk: u32, sample_rate: u32, data_format: DataFormat, mck: bool) -> (u8, bool, u32) {
+    // Clock cycles per frame of the I2S clock generator
+    let cycles = if mck {
+        256
+    } else if data_format == DataFormat::Data16Channel16 {
+        32
+    } else {
+        64
+    };
+    let frame_clk = sample_rate * cycles;
+    let div = (i2sclk + frame_clk / 2) / frame_clk;
+    // I2SDIV must be at least 2
+    assert!((4..=511).contains(&div));
+    ((div / 2) as u8, div % 2 == 1, i2sclk / (cycles * div))
+}
+
+impl<SPI, REMAP, PINS> I2s<SPI, REMAP, PINS>
+where
+    SPI: Instance,
+{
+    fn new(spi: SPI, pins: PINS, config: Config, clocks: Clocks) -> Self
+    where
+        PINS: Pins<REMAP>,
+    {
+        // enable or reset SPI
+        let rcc = unsafe { &(*RCC::ptr()) };
+        SPI::enable(rcc);
+        SPI::reset(rcc);
+
+        let sample_rate = if config.mode.is_master() {
+            let (div, odd, sample_rate) = prescaler(
+                clocks.i2sclk().0,
+                config.sample_rate.0,
+                config.data_format,
+                PINS::MCK,
+            );
+            spi.i2spr
+                .write(|w| unsafe { w.i2sdiv().bits(div).odd().bit(odd).mckoe().bit(PINS::MCK) });
+            Some(Hertz(sample_rate))
+        } else {
+            None
+        };
+
+        spi.i2scfgr.write(|w| {
+            w.i2smod().i2smode();
+            match config.mode {
+                I2sMode::MasterTx => w.i2scfg().master_tx(),
+                I2sMode::MasterRx => w.i2scfg().master_rx(),
+                I2sMode::SlaveTx => w.i2scfg().slave_tx(),
+                I2sMode::SlaveRx => w.i2scfg().slave_rx(),
+            };
+            match config.standard {
+                Standard::Philips => w.i2sstd().philips(),
+                Standard::Msb => w.i2sstd().msb(),
+                Standard::Lsb => w.i2sstd().lsb(),
+                Standard::PcmShort => w.i2sstd().pcm().pcmsync().short(),
+                Standard::PcmLong => w.i2sstd().pcm().pcmsync().long(),
+            };
+            match config.clock_polarity {
+                ClockPolarity::IdleLow => w.ckpol().idle_low(),
+                ClockPolarity::IdleHigh => w.ckpol().idle_high(),
+            };
+            match config.data_format {
+                DataFormat::Data16Channel16 => w.datlen().sixteen_bit().chlen().sixteen_bit(),
+                DataFormat::Data16Channel32 => w.datlen().sixteen_bit().chlen().thirty_two_bit(),
+                DataFormat::Data24Channel32 => {
+                    w.datlen().twenty_four_bit().chlen().thirty_two_bit()
+                }
+                DataFormat::Data32Channel32 => w.datlen().thirty_two_bit().chlen().thirty_two_bit(),
+            }
+        });
+
+        I2s {
+            spi,
+            pins,
+            mode: config.mode,
+            sample_rate,
+            _remap: core::marker::PhantomData,
+        }
+    }
+
+    /// Starts the communication, in master mode the clock is generated from now on
+    ///
+    /// A slave transmitter should have written its first half-word before.
+    pub fn enable(&mut self) {
+        self.spi.i2scfgr.modify(|_, w| w.i2se().enabled());
+    }
+
+    /// Stops the communication
+    ///
+    /// A transmitter waits until the last half-word has been sent.
+    pub fn disable(&mut self) {
+        if matches!(self.mode, I2sMode::MasterTx | I2sMode::SlaveTx) {
+            while self.spi.sr.read().txe().bit_is_clear() {}
+            while self.spi.sr.read().bsy().bit_is_set() {}
+        }
+        self.spi.i2scfgr.modify(|_, w| w.i2se().disabled());
+    }
+
+    /// Returns the sample rate generated in master mode
+    pub fn sample_rate(&self) -> Option<Hertz> {
+        self.sample_rate
+    }
+
+    /// Returns the channel of the next half-word to send, or of the half-word just received
+    ///
+    /// Not meaningful with the PCM standards.
+    pub fn channel(&self) -> Channel {
+        if self.spi.sr.read().chside().bit_is_set() {
+            Channel::Right
+        } else {
+            Channel::Left
+        }
+    }
+
+    /// Sends a half-word
+    pub fn send(&mut self, word: u16) -> nb::Result<(), Error> {
+        let sr = self.spi.sr.read();
+        if sr.udr().bit_is_set() {
+            // UDR is cleared by reading sr
+            Err(nb::Error::Other(Error::Underrun))
+        } else if sr.txe().bit_is_set() {
+            self.spi.dr.write(|w| w.dr().bits(word));
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Receives a half-word
+    pub fn receive(&mut self) -> nb::Result<u16, Error> {
+        let sr = self.spi.sr.read();
+        if sr.ovr().bit_is_set() {
+            // Clear the overrun flag by reading dr followed by sr
+            let _ = self.spi.dr.read();
+            let _ = self.spi.sr.read();
+            Err(nb::Error::Other(Error::Overrun))
+        } else if sr.rxne().bit_is_set() {
+            Ok(self.spi.dr.read().dr().bits())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Sends all half-words of `words`
+    pub fn write(&mut self, words: &[u16]) -> Result<(), Error> {
+        for word in words {
+            block!(self.send(*word))?;
+        }
+        Ok(())
+    }
+
+    /// Fills `words` with received half-words
+    pub fn read(&mut self, words: &mut [u16]) -> Result<(), Error> {
+        for word in words.iter_mut() {
+            *word = block!(self.receive())?;
+        }
+        Ok(())
+    }
+
+    /// Disables the I2S and returns ownership of the SPI peripheral and the pins
+    pub fn release(mut self) -> (SPI, PINS) {
+        self.disable();
+        self.spi.i2scfgr.reset();
+        (self.spi, self.pins)
+    }
+}
+
+impl<REMAP, PINS> I2s<SPI2, REMAP, PINS> {
+    /**
+      Constructs an I2S instance using SPI2.
+
+      The pin parameter tuple (ws, ck, sd, mck) should be `(PB12, PB13, PB15, PC6)`.
+    */
+    pub fn i2s2(spi: SPI2, pins: PINS, config: Config, clocks: Clocks) -> Self
+    where
+        REMAP: Remap<Periph = SPI2>,
+        PINS: Pins<REMAP>,
+    {
+        I2s::new(spi, pins, config, clocks)
+    }
+}
+
+impl<REMAP, PINS> I2s<SPI3, REMAP, PINS> {
+    /**
+      Constructs an I2S instance using SPI3.
+
+      The pin parameter tuple (ws, ck, sd, mck) should be `(PA15, PB3, PB5, PC7)`.
+    */
+    #[cfg(not(feature = "connectivity"))]
+    pub fn i2s3(spi: SPI3, pins: PINS, config: Config, clocks: Clocks) -> Self
+    where
+        REMAP: Remap<Periph = SPI3>,
+        PINS: Pins<REMAP>,
+    {
+        I2s::new(spi, pins, config, clocks)
+    }
+
+    /**
+      Constructs an I2S instance using SPI3.
+
+      The pin parameter tuple (ws, ck, sd, mck) should be `(PA15, PB3, PB5, PC7)` or
+      `(PA4, PC10, PC12, PC7)`.
+    */
+    #[cfg(feature = "connectivity")]
+    pub fn i2s3(spi: SPI3, pins: PINS, mapr: &mut MAPR, config: Config, clocks: Clocks) -> Self
+    where
+        REMAP: Remap<Periph = SPI3>,
+        PINS: Pins<REMAP>,
+    {
+        mapr.modify_mapr(|_, w| w.spi3_remap().bit(REMAP::REMAP));
+        I2s::new(spi, pins, config, clocks)
+    }
+}
+
+pub type I2sRxDma<SPI, REMAP, PINS, CHANNEL> = RxDma<I2s<SPI, REMAP, PINS>, CHANNEL>;
+pub type I2sTxDma<SPI, REMAP, PINS, CHANNEL> = TxDma<I2s<SPI, REMAP, PINS>, CHANNEL>;
+
+macro_rules! i2s_dma {
+    ($SPIi:ident, $RCi:ty, $TCi:ty, $rxdma:ident, $txdma:ident) => {
+        pub type $rxdma<REMAP, PINS> = I2sRxDma<$SPIi, REMAP, PINS, $RCi>;
+        pub type $txdma<REMAP, PINS> = I2sTxDma<$SPIi, REMAP, PINS, $TCi>;
+
+        impl<REMAP, PINS> Receive for I2sRxDma<$SPIi, REMAP, PINS, $RCi> {
+            type RxChannel = $RCi;
+            type TransmittedWord = u16;
+        }
+
+        impl<REMAP, PINS> Transmit for I2sTxDma<$SPIi, REMAP, PINS, $TCi> {
+            type TxChannel = $TCi;
+            type ReceivedWord = u16;
+        }
+
+        impl<REMAP, PINS> I2s<$SPIi, REMAP, PINS> {
+            pub fn with_rx_dma(self, channel: $RCi) -> I2sRxDma<$SPIi, REMAP, PINS, $RCi> {
+                self.spi.cr2.modify(|_, w| w.rxdmaen().set_bit());
+                I2sRxDma {
+                    payload: self,
+                    channel,
+                }
+            }
+            pub fn with_tx_dma(self, channel: $TCi) -> I2sTxDma<$SPIi, REMAP, PINS, $TCi> {
+                self.spi.cr2.modify(|_, w| w.txdmaen().set_bit());
+                I2sTxDma {
+                    payload: self,
+                    channel,
+                }
+            }
+        }
+
+        impl<REMAP, PINS> I2sRxDma<$SPIi, REMAP, PINS, $RCi> {
+            pub fn release(self) -> (I2s<$SPIi, REMAP, PINS>, $RCi) {
+                let I2sRxDma { payload, channel } = self;
+                payload.spi.cr2.modify(|_, w| w.rxdmaen().clear_bit());
+                (payload, channel)
+            }
+        }
+
+        impl<REMAP, PINS> I2sTxDma<$SPIi, REMAP, PINS, $TCi> {
+            pub fn release(self) -> (I2s<$SPIi, REMAP, PINS>, $TCi) {
+                let I2sTxDma { payload, channel } = self;
+                payload.spi.cr2.modify(|_, w| w.txdmaen().clear_bit());
+                (payload, channel)
+            }
+        }
+
+        impl<REMAP, PINS> TransferPayload for I2sRxDma<$SPIi, REMAP, PINS, $RCi> {
+            fn start(&mut self) {
+                self.channel.start();
+            }
+            fn stop(&mut self) {
+                self.channel.stop();
+            }
+        }
+
+        impl<REMAP, PINS> TransferPayload for I2sTxDma<$SPIi, REMAP, PINS, $TCi> {
+            fn start(&mut self) {
+                self.channel.start();
+            }
+            fn stop(&mut self) {
+                self.channel.stop();
+            }
+        }
+
+        impl<B, REMAP, PINS> crate::dma::CircReadDma<B, u16> for I2sRxDma<$SPIi, REMAP, PINS, $RCi>
+        where
+            &'static mut [B; 2]: StaticWriteBuffer<Word = u16>,
+            B: 'static,
+        {
+            /// Receives into both halves of `buffer` in turn and enables the I2S
+            fn circ_read(mut self, mut buffer: &'static mut [B; 2]) -> CircBuffer<B, Self> {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.static_write_buffer() };
+                self.channel.set_peripheral_address(
+                    unsafe { &(*$SPIi::ptr()).dr as *const _ as u32 },
+                    false,
+                );
+                self.channel.set_memory_address(ptr as u32, true);
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.ch().cr.modify(|_, w| {
+                    w
+                        // memory to memory mode disabled
+                        .mem2mem()
+                        .clear_bit()
+                        // high channel priority level, audio must not stall
+                        .pl()
+                        .high()
+                        // 16-bit memory size
+                        .msize()
+                        .bits16()
+                        // 16-bit peripheral size
+                        .psize()
+                        .bits16()
+                        // circular mode enabled
+                        .circ()
+                        .set_bit()
+                        // write to memory
+                        .dir()
+                        .clear_bit()
+                });
+                self.start();
+                self.payload.enable();
+
+                CircBuffer::new(buffer, self)
+            }
+        }
+
+        impl<B, REMAP, PINS> crate::dma::CircWriteDma<B, u16> for I2sTxDma<$SPIi, REMAP, PINS, $TCi>
+        where
+            &'static mut [B; 2]: StaticWriteBuffer<Word = u16>,
+            B: 'static,
+        {
+            /// Sends both halves of `buffer` in turn and enables the I2S
+            ///
+            /// `buffer` should already contain the first data to send.
+            fn circ_write(mut self, mut buffer: &'static mut [B; 2]) -> CircBuffer<B, Self> {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.static_write_buffer() };
+                self.channel.set_peripheral_address(
+                    unsafe { &(*$SPIi::ptr()).dr as *const _ as u32 },
+                    false,
+                );
+                self.channel.set_memory_address(ptr as u32, true);
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.ch().cr.modify(|_, w| {
+                    w
+                        // memory to memory mode disabled
+                        .mem2mem()
+                        .clear_bit()
+                        // high channel priority level, audio must not stall
+                        .pl()
+                        .high()
+                        // 16-bit memory size
+                        .msize()
+                        .bits16()
+                        // 16-bit peripheral size
+                        .psize()
+                        .bits16()
+                        // circular mode enabled
+                        .circ()
+                        .set_bit()
+                        // read from memory
+                        .dir()
+                        .set_bit()
+                });
+                self.start();
+                self.payload.enable();
+
+                CircBuffer::new(buffer, self)
+            }
+        }
+    };
+}
+
+i2s_dma!(SPI2, dma1::C4, dma1::C5, I2s2RxDma, I2s2TxDma);
+i2s_dma!(SPI3, dma2::C1, dma2::C2, I2s3RxDma, I2s3TxDma);
@@ -10,12 +10,16 @@
 
   Not available on STM32F101.
 
-  | Channel | Tim1NoRemap | Tim1FullRemap |
-  |:---:|:-----------:|:-------------:|
-  | CH1 |     PA8     |       PE9     |
-  | CH2 |     PA9     |       PE11    |
-  | CH3 |     PA10    |       PE13    |
-  | CH4 |     PA11    |       PE14    |
+  | Channel | Tim1NoRemap | Tim1PartialRemap | Tim1FullRemap |
+  |:---:|:-----------:|:----------------:|:-------------:|
+  | CH1 |     PA8     |        PA8       |       PE9     |
+  | CH2 |     PA9     |        PA9       |       PE11    |
+  | CH3 |     PA10    |        PA10      |       PE13    |
+  | CH4 |     PA11    |        PA11      |       PE14    |
+  | CH1N |    PB13    |        PA7       |       PE8     |
+  | CH2N |    PB14    |        PB0       |       PE10    |
+  | CH3N |    PB15    |        PB1       |       PE12    |
+  | BKIN |    PB12    |        PA6       |       PE15    |
 
   ### TIM2
 
@@ -45,6 +49,67 @@
   | CH2 |     PB7     |    PD13   |
   | CH3 |     PB8     |    PD14   |
   | CH4 |     PB9     |    PD15   |
+
+  ### TIM5
+
+  Only available on high density and connectivity line devices.
+
+  | Channel | Tim5NoRemap |
+  |:---:|:-----------:|
+  | CH1 |     PA0     |
+  | CH2 |     PA1     |
+  | CH3 |     PA2     |
+  | CH4 |     PA3     |
+
+  ### TIM15, TIM16 and TIM17
+
+  Only available on STM32F100 (value line) devices. The complementary outputs and the break
+  inputs are not remapped.
+
+  | Channel | Tim15NoRemap | Tim15Remap |
+  |:---:|:-----------:|:---------:|
+  | CH1 |     PA2     |    PB14   |
+  | CH2 |     PA3     |    PB15   |
+  | CH1N |    PA1     |    PA1    |
+  | BKIN |    PA9     |    PA9    |
+
+  | Channel | Tim16NoRemap | Tim16Remap | Tim17NoRemap | Tim17Remap |
+  |:---:|:-----------:|:---------:|:-----------:|:---------:|
+  | CH1 |     PB8     |    PA6    |     PB9     |    PA7    |
+  | CH1N |    PB6     |    PB6    |     PB7     |    PB7    |
+  | BKIN |    PB5     |    PB5    |     PB4     |    PB4    |
+
+  ### TIM8
+
+  Only available on high density STM32F103 devices.
+
+  | Channel | Tim8NoRemap |
+  |:---:|:-----------:|
+  | CH1 |     PC6     |
+  | CH2 |     PC7     |
+  | CH3 |     PC8     |
+  | CH4 |     PC9     |
+  | CH1N |    PA7     |
+  | CH2N |    PB0     |
+  | CH3N |    PB1     |
+  | BKIN |    PA6     |
+
+  ### TIM9 to TIM14
+
+  Only available on XL density devices. TIM12 can't be remapped.
+
+  | Channel | Tim9NoRemap | Tim9Remap | Tim12NoRemap |
+  |:---:|:-----------:|:---------:|:------------:|
+  | CH1 |     PA2     |    PE5    |     PB14     |
+  | CH2 |     PA3     |    PE6    |     PB15     |
+
+  | Channel | Tim10NoRemap | Tim10Remap | Tim11NoRemap | Tim11Remap |
+  |:---:|:-----------:|:---------:|:-----------:|:---------:|
+  | CH1 |     PB8     |    PF6    |     PB9     |    PF7    |
+
+  | Channel | Tim13NoRemap | Tim13Remap | Tim14NoRemap | Tim14Remap |
+  |:---:|:-----------:|:---------:|:-----------:|:---------:|
+  | CH1 |     PA6     |    PF8    |     PA7     |    PF9    |
 */
 
 use crate::hal::timer::{Cancel, CountDown, Periodic};
@@ -65,6 +130,10 @@ use crate::pac::TIM7;
 #[cfg(all(feature = "stm32f103", feature = "high",))]
 use crate::pac::TIM8;
 use crate::pac::{DBGMCU as DBG, TIM2, TIM3};
+#[cfg(feature = "xl")]
+use crate::pac::{TIM10, TIM11, TIM9};
+#[cfg(any(feature = "xl", all(feature = "stm32f100", feature = "high",)))]
+use crate::pac::{TIM12, TIM13, TIM14};
 #[cfg(feature = "stm32f100")]
 use crate::pac::{TIM15, TIM16, TIM17};
 
@@ -107,6 +176,10 @@ pub(crate) mod sealed {
     pub trait Ch2<REMAP> {}
     pub trait Ch3<REMAP> {}
     pub trait Ch4<REMAP> {}
+    pub trait Ch1N<REMAP> {}
+    pub trait Ch2N<REMAP> {}
+    pub trait Ch3N<REMAP> {}
+    pub trait Bkin<REMAP> {}
 }
 
 macro_rules! remap {
@@ -125,6 +198,36 @@ macro_rules! remap {
     }
 }
 
+/// Timers with less than 4 channels
+#[cfg(any(feature = "stm32f100", feature = "xl"))]
+macro_rules! remap_channels {
+    ($($name:ident: ($TIMX:ident, $state:literal, [$($P:ident: $Ch:ident),+]),)+) => {
+        $(
+            pub struct $name;
+            impl sealed::Remap for $name {
+                type Periph = $TIMX;
+                const REMAP: u8 = $state;
+            }
+            $(
+                impl<MODE> sealed::$Ch<$name> for $P<MODE> {}
+            )+
+        )+
+    }
+}
+
+/// Complementary outputs and break input of the advanced control timers
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+macro_rules! remap_complementary {
+    ($($name:ident: ($P1N:ident, $P2N:ident, $P3N:ident, $BKIN:ident),)+) => {
+        $(
+            impl<MODE> sealed::Ch1N<$name> for $P1N<MODE> {}
+            impl<MODE> sealed::Ch2N<$name> for $P2N<MODE> {}
+            impl<MODE> sealed::Ch3N<$name> for $P3N<MODE> {}
+            impl<MODE> sealed::Bkin<$name> for $BKIN<MODE> {}
+        )+
+    }
+}
+
 use crate::gpio::gpioa::{PA0, PA1, PA15, PA2, PA3, PA6, PA7};
 use crate::gpio::gpiob::{PB0, PB1, PB10, PB11, PB3, PB4, PB5};
 use crate::gpio::gpioc::{PC6, PC7, PC8, PC9};
@@ -132,14 +235,21 @@ use crate::gpio::gpioc::{PC6, PC7, PC8, PC9};
 #[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
 use crate::gpio::{
     gpioa::{PA10, PA11, PA8, PA9},
-    gpioe::{PE11, PE13, PE14, PE9},
+    gpiob::{PB12, PB13, PB14, PB15},
+    gpioe::{PE10, PE11, PE12, PE13, PE14, PE15, PE8, PE9},
 };
 #[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
 remap!(
     Tim1NoRemap: (TIM1, 0b00, PA8, PA9, PA10, PA11),
-    //Tim1PartialRemap: (TIM1, 0b01, PA8, PA9, PA10, PA11),
+    Tim1PartialRemap: (TIM1, 0b01, PA8, PA9, PA10, PA11),
     Tim1FullRemap: (TIM1, 0b11, PE9, PE11, PE13, PE14),
 );
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+remap_complementary!(
+    Tim1NoRemap: (PB13, PB14, PB15, PB12),
+    Tim1PartialRemap: (PA7, PB0, PB1, PA6),
+    Tim1FullRemap: (PE8, PE10, PE12, PE15),
+);
 
 remap!(
     Tim2NoRemap: (TIM2, 0b00, PA0, PA1, PA2, PA3),
@@ -163,6 +273,55 @@ remap!(
     Tim4Remap: (TIM4, 0b01, PD12, PD13, PD14, PD15),
 );
 
+// TIM5 and TIM8 can't be remapped
+#[cfg(any(feature = "high", feature = "connectivity"))]
+remap!(
+    Tim5NoRemap: (TIM5, 0b00, PA0, PA1, PA2, PA3),
+);
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+remap!(
+    Tim8NoRemap: (TIM8, 0b00, PC6, PC7, PC8, PC9),
+);
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+remap_complementary!(
+    Tim8NoRemap: (PA7, PB0, PB1, PA6),
+);
+
+#[cfg(all(feature = "stm32f100", not(feature = "medium")))]
+use crate::gpio::gpiob::{PB6, PB7, PB8, PB9};
+#[cfg(feature = "stm32f100")]
+remap_channels!(
+    Tim15NoRemap: (TIM15, 0, [PA2: Ch1, PA3: Ch2, PA1: Ch1N, PA9: Bkin]),
+    Tim15Remap: (TIM15, 1, [PB14: Ch1, PB15: Ch2, PA1: Ch1N, PA9: Bkin]),
+    Tim16NoRemap: (TIM16, 0, [PB8: Ch1, PB6: Ch1N, PB5: Bkin]),
+    Tim16Remap: (TIM16, 1, [PA6: Ch1, PB6: Ch1N, PB5: Bkin]),
+    Tim17NoRemap: (TIM17, 0, [PB9: Ch1, PB7: Ch1N, PB4: Bkin]),
+    Tim17Remap: (TIM17, 1, [PA7: Ch1, PB7: Ch1N, PB4: Bkin]),
+);
+
+#[cfg(all(feature = "stm32f101", feature = "xl"))]
+use crate::gpio::gpiob::{PB14, PB15};
+#[cfg(feature = "xl")]
+use crate::gpio::{
+    gpioe::{PE5, PE6},
+    gpiof::{PF6, PF7, PF8, PF9},
+};
+#[cfg(feature = "xl")]
+remap_channels!(
+    Tim9NoRemap: (TIM9, 0, [PA2: Ch1, PA3: Ch2]),
+    Tim9Remap: (TIM9, 1, [PE5: Ch1, PE6: Ch2]),
+    Tim10NoRemap: (TIM10, 0, [PB8: Ch1]),
+    Tim10Remap: (TIM10, 1, [PF6: Ch1]),
+    Tim11NoRemap: (TIM11, 0, [PB9: Ch1]),
+    Tim11Remap: (TIM11, 1, [PF7: Ch1]),
+    Tim12NoRemap: (TIM12, 0, [PB14: Ch1, PB15: Ch2]),
+    Tim13NoRemap: (TIM13, 0, [PA6: Ch1]),
+    Tim13Remap: (TIM13, 1, [PF8: Ch1]),
+    Tim14NoRemap: (TIM14, 0, [PA7: Ch1]),
+    Tim14Remap: (TIM14, 1, [PF9: Ch1]),
+);
+
 impl Timer<SYST> {
     pub fn syst(mut syst: SYST, clocks: &Clocks) -> Self {
         syst.set_clock_source(SystClkSource::Core);
@@ -277,6 +436,23 @@ impl Periodic for CountDownTimer<SYST> {}
 
 macro_rules! hal {
     ($($TIMX:ident: ($timX:ident, $APBx:ident, $dbg_timX_stop:ident$(,$master_timbase:ident)*),)+) => {
+        hal!(@timer $($TIMX: ($timX, $APBx$(,$master_timbase)*),)+);
+
+        $(
+            impl Timer<$TIMX> {
+                /// Stopping timer in debug mode can cause troubles when sampling the signal
+                #[inline(always)]
+                pub fn stop_in_debug(&mut self, dbg: &mut DBG, state: bool) {
+                    dbg.cr.modify(|_, w| w.$dbg_timX_stop().bit(state));
+                }
+            }
+        )+
+    };
+    // Timers whose debug freeze bit is missing from the device crate
+    ($($TIMX:ident: ($timX:ident, $APBx:ident),)+) => {
+        hal!(@timer $($TIMX: ($timX, $APBx),)+);
+    };
+    (@timer $($TIMX:ident: ($timX:ident, $APBx:ident$(,$master_timbase:ident)*),)+) => {
         $(
             impl Timer<$TIMX> {
                 /// Initialize timer
@@ -330,12 +506,6 @@ macro_rules! hal {
                     $TIMX::reset(rcc);
                 }
 
-                /// Stopping timer in debug mode can cause troubles when sampling the signal
-                #[inline(always)]
-                pub fn stop_in_debug(&mut self, dbg: &mut DBG, state: bool) {
-                    dbg.cr.modify(|_, w| w.$dbg_timX_stop().bit(state));
-                }
-
                 /// Releases the TIM Peripheral
                 pub fn release(self) -> $TIMX {
                     self.tim
@@ -531,25 +701,25 @@ hal! {
     TIM8: (tim8, APB2, dbg_tim8_stop, tim1),
 }
 
-//TODO: restore these timers once stm32-rs has been updated
-/*
- *   dbg_tim(12-13)_stop fields missing from 103 xl in stm32-rs
- *   dbg_tim(9-10)_stop fields missing from 101 xl in stm32-rs
+// The device crate is missing the debug freeze bits of these timers on some parts
+#[cfg(all(feature = "stm32f103", feature = "xl"))]
+hal! {
+    TIM12: (tim12, APB1),
+    TIM13: (tim13, APB1),
+    TIM14: (tim14, APB1),
+}
 #[cfg(any(
-    feature = "xl",
-    all(
-        feature = "stm32f100",
-        feature = "high",
-)))]
+    all(feature = "stm32f101", feature = "xl"),
+    all(feature = "stm32f100", feature = "high")
+))]
 hal! {
-    TIM12: (tim12, dbg_tim12_stop),
-    TIM13: (tim13, dbg_tim13_stop),
-    TIM14: (tim14, dbg_tim14_stop),
+    TIM12: (tim12, APB1, dbg_tim12_stop),
+    TIM13: (tim13, APB1, dbg_tim13_stop),
+    TIM14: (tim14, APB1, dbg_tim14_stop),
 }
 #[cfg(feature = "xl")]
 hal! {
-    TIM9: (tim9, dbg_tim9_stop),
-    TIM10: (tim10, dbg_tim10_stop),
-    TIM11: (tim11, dbg_tim11_stop),
+    TIM9: (tim9, APB2),
+    TIM10: (tim10, APB2),
+    TIM11: (tim11, APB2),
 }
-*/